@@ -0,0 +1,69 @@
+/*
+Entries can be configured (see `Config::with_absence_ttl`) to remember,
+for a short time, that their credential wasn't found, so that polling
+for an optional credential doesn't go back to secure storage (and,
+on Mac, risk a prompt) each time.  While a credential is remembered as
+absent, reading its password fails with `NoEntry` immediately.
+
+The memory is shared by all the entries in the process that have been
+configured this way, and is keyed by credential.  Each absence is
+remembered for the time configured for the entry that found it.
+Setting a password through any entry forgets that its credential is
+absent, but a credential created by another process in the meantime
+isn't seen until the memory of its absence expires.
+ */
+
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::credential::PlatformCredential;
+
+static ABSENT: Mutex<Vec<(PlatformCredential, Instant)>> = Mutex::new(Vec::new());
+
+// Whether the credential is remembered as absent.
+pub(crate) fn is_known_absent(target: &PlatformCredential) -> bool {
+    let mut absent = absent();
+    let now = Instant::now();
+    absent.retain(|(_, until)| *until > now);
+    absent.iter().any(|(credential, _)| credential == target)
+}
+
+// Remember, for the given time, that the credential was just found to be
+// absent.
+pub(crate) fn remember(target: &PlatformCredential, ttl: Duration) {
+    let mut absent = absent();
+    absent.retain(|(credential, _)| credential != target);
+    absent.push((target.clone(), Instant::now() + ttl));
+}
+
+// Forget that the credential was found to be absent, if it was.
+pub(crate) fn forget(target: &PlatformCredential) {
+    absent().retain(|(credential, _)| credential != target);
+}
+
+fn absent() -> MutexGuard<'static, Vec<(PlatformCredential, Instant)>> {
+    ABSENT.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credential::{default_target, Platform};
+
+    #[test]
+    fn test_remember_and_forget() {
+        let target = default_target(&Platform::Linux, None, "absence", "remembered");
+        let other = default_target(&Platform::Linux, None, "absence", "other");
+        let ttl = Duration::from_secs(60);
+        assert!(!is_known_absent(&target));
+        remember(&target, ttl);
+        assert!(is_known_absent(&target));
+        assert!(!is_known_absent(&other));
+        forget(&target);
+        assert!(!is_known_absent(&target));
+        // remembering again replaces the earlier memory
+        remember(&target, ttl);
+        remember(&target, Duration::ZERO);
+        assert!(!is_known_absent(&target));
+    }
+}
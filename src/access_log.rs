@@ -0,0 +1,204 @@
+/*
+An entry can optionally keep a log of the times its password was
+retrieved.  Each successful read through the entry appends an access
+record (a timestamp and an optional caller-supplied tag) to the log.
+The log is kept in the credential's access-log companion, so any
+process that can read the credential can read its log.
+
+The log is bounded: once it holds its capacity of records, the oldest
+record is dropped for each new one.  The log is stored as the
+companion's password, so on platforms that limit the length of a
+password (Windows) its capacity is also limited to the records that
+fit, and older records are dropped as needed to make the log fit.  The
+log never contains the password.
+ */
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::credential::PlatformCredential;
+use crate::{platform, Error, Result};
+
+pub(crate) const PURPOSE: &str = "access-log";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessRecord {
+    pub time: SystemTime,
+    pub tag: Option<String>,
+}
+
+// The access log settings of an entry.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct AccessLog {
+    pub capacity: usize,
+    pub tag: Option<String>,
+}
+
+impl AccessLog {
+    // Settings for a log of the given capacity, limited to the number of
+    // records with the given tag that fit in a password.
+    pub fn new(capacity: usize, tag: Option<&str>) -> AccessLog {
+        let record = AccessRecord {
+            time: SystemTime::now(),
+            tag: tag.map(String::from),
+        };
+        let record_len = platform::password_len(&format_records(&[record]));
+        let capacity = match platform::password_capacity() {
+            Some(limit) => capacity.min(limit / record_len),
+            None => capacity,
+        };
+        AccessLog {
+            capacity,
+            tag: tag.map(String::from),
+        }
+    }
+
+    // Append a record of an access happening now.
+    pub fn record(&self, target: &PlatformCredential) -> Result<()> {
+        let mut records = read(target)?;
+        records.push(AccessRecord {
+            time: SystemTime::now(),
+            tag: self.tag.clone(),
+        });
+        if records.len() > self.capacity {
+            records.drain(..records.len() - self.capacity);
+        }
+        // records written with longer tags (by other entries) take up more
+        // room, so the log may still be too long to store
+        let log = format_fitted(&mut records, platform::password_capacity());
+        platform::set_password(&target.companion(PURPOSE), &log)
+    }
+}
+
+// Format the records, first dropping the oldest ones until the log fits
+// in the given number of bytes (if there is a limit).
+fn format_fitted(records: &mut Vec<AccessRecord>, limit: Option<usize>) -> String {
+    let mut log = format_records(records);
+    while limit.is_some_and(|limit| platform::password_len(&log) > limit) {
+        records.remove(0);
+        log = format_records(records);
+    }
+    log
+}
+
+// Read the access log for a credential.  A credential that has never
+// been accessed with logging enabled has an empty log.
+pub(crate) fn read(target: &PlatformCredential) -> Result<Vec<AccessRecord>> {
+    match platform::get_password(&mut target.companion(PURPOSE)) {
+        Ok(log) => Ok(parse_records(&log)),
+        Err(Error::NoEntry) => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+// Remove the access log for a credential, if there is one.
+pub(crate) fn delete(target: &PlatformCredential) -> Result<()> {
+    match platform::delete_password(&target.companion(PURPOSE)) {
+        Ok(()) | Err(Error::NoEntry) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+// Each record is stored on its own line as the seconds and nanoseconds
+// since the epoch, followed by a tab and the tag if there is one.
+fn format_records(records: &[AccessRecord]) -> String {
+    let mut log = String::new();
+    for record in records {
+        let since = record
+            .time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO);
+        log.push_str(&format!("{}.{:09}", since.as_secs(), since.subsec_nanos()));
+        if let Some(tag) = &record.tag {
+            let tag: String = tag
+                .chars()
+                .map(|c| if c.is_control() { ' ' } else { c })
+                .collect();
+            log.push('\t');
+            log.push_str(&tag);
+        }
+        log.push('\n');
+    }
+    log
+}
+
+// Lines that can't be parsed are skipped rather than failing the whole log.
+fn parse_records(log: &str) -> Vec<AccessRecord> {
+    log.lines()
+        .filter_map(|line| {
+            let (time, tag) = match line.split_once('\t') {
+                Some((time, tag)) => (time, Some(tag.to_string())),
+                None => (line, None),
+            };
+            let (secs, nanos) = time.split_once('.')?;
+            let since = Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+            Some(AccessRecord {
+                time: UNIX_EPOCH + since,
+                tag,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_records_round_trip() {
+        let records = vec![
+            AccessRecord {
+                time: UNIX_EPOCH + Duration::new(1_600_000_000, 123),
+                tag: None,
+            },
+            AccessRecord {
+                time: UNIX_EPOCH + Duration::new(1_600_000_001, 0),
+                tag: Some("request 17".to_string()),
+            },
+        ];
+        assert_eq!(parse_records(&format_records(&records)), records);
+    }
+
+    #[test]
+    fn test_tags_cannot_break_records() {
+        let records = vec![AccessRecord {
+            time: UNIX_EPOCH,
+            tag: Some("two\nlines\tand a tab".to_string()),
+        }];
+        let parsed = parse_records(&format_records(&records));
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].tag.as_deref(), Some("two lines and a tab"));
+    }
+
+    #[test]
+    fn test_oldest_records_dropped_to_fit() {
+        let record = |secs, tag: &str| AccessRecord {
+            time: UNIX_EPOCH + Duration::new(secs, 0),
+            tag: Some(tag.to_string()),
+        };
+        let mut records = vec![record(1, "a long tag"), record(2, "b"), record(3, "c")];
+        let limit = platform::password_len(&format_records(&records[1..]));
+        let log = format_fitted(&mut records, Some(limit));
+        assert_eq!(parse_records(&log), [record(2, "b"), record(3, "c")]);
+        assert_eq!(records.len(), 2);
+        let log = format_fitted(&mut records, None);
+        assert_eq!(parse_records(&log).len(), 2);
+        assert_eq!(format_fitted(&mut records, Some(0)), "");
+    }
+
+    #[test]
+    fn test_capacity_limited_to_platform() {
+        let log = AccessLog::new(usize::MAX, Some("tag"));
+        match platform::password_capacity() {
+            Some(limit) => assert!(log.capacity < limit),
+            None => assert_eq!(log.capacity, usize::MAX),
+        }
+        assert_eq!(AccessLog::new(3, None).capacity, 3);
+    }
+
+    #[test]
+    fn test_malformed_lines_skipped() {
+        let parsed = parse_records("garbage\n12.5\tok\n\n1.x\n");
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].tag.as_deref(), Some("ok"));
+    }
+}
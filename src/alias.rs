@@ -0,0 +1,89 @@
+/*
+Aliases let an application refer to its credentials by stable names of
+its own choosing (say, `primary-db`), rather than by how they are
+stored.  Each alias maps to a credential (see `set_alias`), and
+`Entry::by_alias` creates an entry for the credential an alias maps to,
+so the credential behind an alias can be changed (by configuration,
+say) without changing the code that uses it.
+
+The aliases are kept in memory, for the whole process.  They can be
+saved with `serialize_aliases` and loaded again with `load_aliases`;
+the serialized form has one alias per line, with the alias and its
+credential's serialized form (see `PlatformCredential::to_serialized`)
+escaped and separated by `=`.
+ */
+
+use std::sync::{Mutex, MutexGuard};
+
+use crate::credential::PlatformCredential;
+use crate::serialized::{escape, unescape};
+use crate::{Error, Result};
+
+static ALIASES: Mutex<Vec<(String, PlatformCredential)>> = Mutex::new(Vec::new());
+
+// Map the alias to the given credential, replacing any credential it
+// mapped to before.  The credential must be for the running platform.
+pub fn set_alias(alias: &str, credential: &PlatformCredential) -> Result<()> {
+    if !credential.matches_platform(&crate::platform()) {
+        return Err(Error::WrongCredentialPlatform);
+    }
+    let mut aliases = aliases();
+    aliases.retain(|(name, _)| name != alias);
+    aliases.push((alias.to_string(), credential.clone()));
+    Ok(())
+}
+
+// Remove the alias, returning whether there was one.
+pub fn remove_alias(alias: &str) -> bool {
+    let mut aliases = aliases();
+    let count = aliases.len();
+    aliases.retain(|(name, _)| name != alias);
+    aliases.len() != count
+}
+
+// The credential the alias maps to, failing with `UnknownAlias` if
+// there's no such alias.
+pub(crate) fn resolve(alias: &str) -> Result<PlatformCredential> {
+    aliases()
+        .iter()
+        .find(|(name, _)| name == alias)
+        .map(|(_, credential)| credential.clone())
+        .ok_or_else(|| Error::UnknownAlias(alias.to_string()))
+}
+
+// Serialize all the aliases, in the order they were set.
+pub fn serialize_aliases() -> String {
+    let mut serialized = String::new();
+    for (alias, credential) in aliases().iter() {
+        serialized.push_str(&format!(
+            "{}={}\n",
+            escape(alias),
+            escape(&credential.to_serialized())
+        ));
+    }
+    serialized
+}
+
+// Replace all the aliases with the serialized ones.  If any of them
+// can't be loaded (because it isn't well formed, or its credential is
+// for another platform), none of them are, and the aliases are left
+// as they were.
+pub fn load_aliases(serialized: &str) -> Result<()> {
+    let mut loaded = Vec::new();
+    for line in serialized.lines().filter(|line| !line.is_empty()) {
+        let malformed = || Error::BadFormat(format!("alias line '{}'", line));
+        let (alias, credential) = line.split_once('=').ok_or_else(malformed)?;
+        let alias = unescape(alias).ok_or_else(malformed)?;
+        let credential = unescape(credential).ok_or_else(malformed)?;
+        let credential = PlatformCredential::from_serialized(&credential)?;
+        // a later line for the same alias wins, as with `set_alias`
+        loaded.retain(|(name, _)| *name != alias);
+        loaded.push((alias, credential));
+    }
+    *aliases() = loaded;
+    Ok(())
+}
+
+fn aliases() -> MutexGuard<'static, Vec<(String, PlatformCredential)>> {
+    ALIASES.lock().unwrap_or_else(|e| e.into_inner())
+}
@@ -0,0 +1,337 @@
+/*
+A cache file keeps copies of recently read passwords on disk, encrypted,
+so that an application which reads many credentials at startup can
+read them from the cache on its next start instead of from secure
+storage (which, on Mac, can mean keychain work and prompts for each).
+
+The cache is encrypted (with ChaCha20-Poly1305) by a random key that
+is itself kept in secure storage, in a companion credential named for
+the cache file.  So opening a cache reads one credential from secure
+storage (and may prompt for it once, on Mac), after which every
+password in the cache can be read without touching secure storage.
+If the key is lost, the cache is simply empty.
+
+Freshness: a cached password is only used if it was read from secure
+storage less than the cache's maximum age ago.  Setting or deleting a
+password through an entry that uses the cache invalidates its cached
+copy.  Changes made outside the crate (or through entries that don't
+use the cache) are only noticed once the cached copy goes stale.
+
+The file starts with a header giving the version of its format.  Files
+in older formats are read transparently (and rewritten in the current
+format the next time the cache changes), but a file in a newer format
+than this version of the crate understands can't be opened: it fails
+with `UnsupportedFileVersion`, rather than being overwritten.
+
+The cache is only an optimization, so a cache that can't be decrypted
+or parsed is treated as empty, and a failure to write a newly read
+password to it is ignored.  But a failure to write an invalidation is
+an error, since the cache would otherwise go on returning the old
+password.
+
+This module is only available with the `cache-file` feature.
+ */
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+use crate::credential::{default_target, PlatformCredential};
+use crate::serialized::{escape, unescape};
+use crate::{platform, zeroing, Error, Result};
+
+const MAGIC: &str = "keyring-rs cache v";
+const FORMAT_VERSION: u32 = 1;
+const NONCE_LEN: usize = 12;
+
+pub struct CacheFile {
+    path: PathBuf,
+    cipher: ChaCha20Poly1305,
+    max_age: Duration,
+    entries: Mutex<HashMap<String, (SystemTime, String)>>,
+}
+
+impl std::fmt::Debug for CacheFile {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheFile")
+            .field("path", &self.path)
+            .field("max_age", &self.max_age)
+            .finish()
+    }
+}
+
+impl CacheFile {
+    // Open (or start) the cache file at the given path, whose cached
+    // passwords are used for at most `max_age` after they were read.
+    // This reads the cache's key from secure storage, creating the key
+    // (and discarding any existing cache) if there isn't one.
+    pub fn open(path: &Path, max_age: Duration) -> Result<CacheFile> {
+        let key = key_credential(path);
+        let key = match platform::get_password(&mut key.clone()) {
+            Ok(hex) => decode_key(&hex),
+            Err(Error::NoEntry) => None,
+            Err(err) => return Err(err),
+        };
+        let key = match key {
+            Some(key) => key,
+            None => {
+                let mut fresh = [0u8; 32];
+                getrandom::getrandom(&mut fresh)
+                    .map_err(|err| Error::Io(std::io::Error::other(err)))?;
+                platform::set_password(&key_credential(path), &encode_key(&fresh))?;
+                remove_file(path)?;
+                fresh
+            }
+        };
+        CacheFile::with_key(path, &key, max_age)
+    }
+
+    fn with_key(path: &Path, key: &[u8; 32], max_age: Duration) -> Result<CacheFile> {
+        let cache = CacheFile {
+            path: path.to_path_buf(),
+            cipher: ChaCha20Poly1305::new(Key::from_slice(key)),
+            max_age,
+            entries: Mutex::new(HashMap::new()),
+        };
+        if let Ok(contents) = fs::read(path) {
+            if let Some((version, _)) = split_header(&contents) {
+                if version > FORMAT_VERSION {
+                    return Err(Error::UnsupportedFileVersion(version));
+                }
+            }
+            *cache.entries() = cache.decrypt(&contents).unwrap_or_default();
+        }
+        Ok(cache)
+    }
+
+    // Remove the cache file and its key from secure storage.
+    pub fn destroy(self) -> Result<()> {
+        remove_file(&self.path)?;
+        match platform::delete_password(&key_credential(&self.path)) {
+            Ok(()) | Err(Error::NoEntry) => Ok(()),
+            Err(err) => Err(err),
+        }
+    }
+
+    // The cached password for a credential, if there is a fresh one.
+    pub(crate) fn get(&self, target: &PlatformCredential) -> Option<String> {
+        let entries = self.entries();
+        let (read, password) = entries.get(&target.to_serialized())?;
+        self.is_fresh(*read).then(|| password.clone())
+    }
+
+    // Cache a password just read from secure storage.  Stale passwords
+    // are dropped from the cache at the same time.
+    pub(crate) fn put(&self, target: &PlatformCredential, password: &str) {
+        let mut entries = self.entries();
+        entries.retain(|_, (read, _)| self.is_fresh(*read));
+        entries.insert(
+            target.to_serialized(),
+            (SystemTime::now(), password.to_string()),
+        );
+        // caching is best-effort
+        let _ = self.write(&entries);
+    }
+
+    // Forget the cached password for a credential.
+    pub(crate) fn invalidate(&self, target: &PlatformCredential) -> Result<()> {
+        let mut entries = self.entries();
+        if entries.remove(&target.to_serialized()).is_some() {
+            self.write(&entries)?;
+        }
+        Ok(())
+    }
+
+    fn is_fresh(&self, read: SystemTime) -> bool {
+        matches!(SystemTime::now().duration_since(read), Ok(age) if age < self.max_age)
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, HashMap<String, (SystemTime, String)>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // The file is the header, a random nonce, and the encrypted entries,
+    // one per line: the serialized credential, the time it was read, and
+    // its password, all escaped and separated by `=`.  The header is
+    // authenticated along with the entries.
+    fn write(&self, entries: &HashMap<String, (SystemTime, String)>) -> Result<()> {
+        let mut plaintext = String::new();
+        for (locator, (read, password)) in entries {
+            let read = read.duration_since(UNIX_EPOCH).unwrap_or(Duration::ZERO);
+            plaintext.push_str(&format!(
+                "{}={}={}\n",
+                escape(locator),
+                read.as_secs(),
+                escape(password)
+            ));
+        }
+        let mut nonce = [0u8; NONCE_LEN];
+        getrandom::getrandom(&mut nonce).map_err(|err| Error::Io(std::io::Error::other(err)))?;
+        let header = header(FORMAT_VERSION);
+        let payload = Payload {
+            msg: plaintext.as_bytes(),
+            aad: &header,
+        };
+        let encrypted = self.cipher.encrypt(Nonce::from_slice(&nonce), payload);
+        zeroing::wipe(&mut plaintext);
+        let encrypted = encrypted.map_err(|_| Error::BadFormat("cache encryption".to_string()))?;
+        let contents = [&header, &nonce[..], &encrypted].concat();
+        // write to a temporary file, so readers never see a partial cache
+        let temporary = self.path.with_extension("tmp");
+        fs::write(&temporary, contents).map_err(Error::Io)?;
+        fs::rename(&temporary, &self.path).map_err(Error::Io)
+    }
+
+    // Every format so far is version 1; later versions will be decoded
+    // according to the version in the header.
+    fn decrypt(&self, contents: &[u8]) -> Option<HashMap<String, (SystemTime, String)>> {
+        let (version, body) = split_header(contents)?;
+        if version != 1 || body.len() < NONCE_LEN {
+            return None;
+        }
+        let (nonce, encrypted) = body.split_at(NONCE_LEN);
+        let header = header(version);
+        let payload = Payload {
+            msg: encrypted,
+            aad: &header,
+        };
+        let decrypted = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .ok()?;
+        let mut plaintext = String::from_utf8(decrypted).ok()?;
+        let entries = plaintext
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split('=');
+                let locator = unescape(fields.next()?)?;
+                let read = UNIX_EPOCH + Duration::from_secs(fields.next()?.parse().ok()?);
+                let password = unescape(fields.next()?)?;
+                Some((locator, (read, password)))
+            })
+            .collect();
+        zeroing::wipe(&mut plaintext);
+        Some(entries)
+    }
+}
+
+fn header(version: u32) -> Vec<u8> {
+    format!("{}{}\n", MAGIC, version).into_bytes()
+}
+
+// The format version given in a file's header, and the rest of the file.
+fn split_header(contents: &[u8]) -> Option<(u32, &[u8])> {
+    let contents = contents.strip_prefix(MAGIC.as_bytes())?;
+    let end = contents.iter().position(|&byte| byte == b'\n')?;
+    let version = std::str::from_utf8(&contents[..end]).ok()?.parse().ok()?;
+    Some((version, &contents[end + 1..]))
+}
+
+// The credential holding a cache file's key.
+fn key_credential(path: &Path) -> PlatformCredential {
+    let owner = default_target(
+        &crate::platform(),
+        None,
+        &path.display().to_string(),
+        "cache-file",
+    );
+    owner.companion("cache-key")
+}
+
+fn encode_key(key: &[u8; 32]) -> String {
+    key.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn decode_key(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut key = [0u8; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+fn remove_file(path: &Path) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(Error::Io(err)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credential::Platform;
+
+    fn temporary_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("keyring-rs-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn test_persists_across_opens() {
+        let path = temporary_path("persists");
+        let target = default_target(&Platform::Linux, None, "service", "user");
+        let key = [7u8; 32];
+        let cache = CacheFile::with_key(&path, &key, Duration::from_secs(60)).unwrap();
+        cache.put(&target, "cached=password\n");
+        let reopened = CacheFile::with_key(&path, &key, Duration::from_secs(60)).unwrap();
+        assert_eq!(reopened.get(&target).as_deref(), Some("cached=password\n"));
+        reopened.invalidate(&target).unwrap();
+        let reopened = CacheFile::with_key(&path, &key, Duration::from_secs(60)).unwrap();
+        assert!(reopened.get(&target).is_none());
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_stale_or_undecryptable_ignored() {
+        let path = temporary_path("stale");
+        let target = default_target(&Platform::Linux, None, "service", "user");
+        let cache = CacheFile::with_key(&path, &[1u8; 32], Duration::ZERO).unwrap();
+        cache.put(&target, "password");
+        assert!(cache.get(&target).is_none());
+        let cache = CacheFile::with_key(&path, &[1u8; 32], Duration::from_secs(60)).unwrap();
+        cache.put(&target, "password");
+        let contents = fs::read(&path).unwrap();
+        assert!(!contents.windows(8).any(|window| window == b"password"));
+        let wrong_key = CacheFile::with_key(&path, &[2u8; 32], Duration::from_secs(60)).unwrap();
+        assert!(wrong_key.get(&target).is_none());
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_format_versions() {
+        let path = temporary_path("versions");
+        let target = default_target(&Platform::Linux, None, "service", "user");
+        let key = [3u8; 32];
+        let cache = CacheFile::with_key(&path, &key, Duration::from_secs(60)).unwrap();
+        cache.put(&target, "password");
+        let contents = fs::read(&path).unwrap();
+        assert_eq!(split_header(&contents).map(|(version, _)| version), Some(1));
+        // a file from a later version of the crate is left alone
+        fs::write(&path, b"keyring-rs cache v2\nfrom the future").unwrap();
+        assert!(matches!(
+            CacheFile::with_key(&path, &key, Duration::from_secs(60)),
+            Err(Error::UnsupportedFileVersion(2))
+        ));
+        assert_eq!(
+            fs::read(&path).unwrap(),
+            b"keyring-rs cache v2\nfrom the future"
+        );
+        assert!(split_header(b"keyring-rs cache vx\n").is_none());
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_key_encoding() {
+        let key = [0xa5; 32];
+        assert_eq!(decode_key(&encode_key(&key)), Some(key));
+        assert_eq!(decode_key("not a key"), None);
+    }
+}
@@ -0,0 +1,123 @@
+/*
+The capabilities of the platform's secure storage are the things this
+crate can do with some platforms' storage and not with others'.  An
+application that depends on one of them can check at startup, with
+`require_capabilities`, that the platform it's running on has it,
+rather than finding out from the first operation that needs it.
+
+- `enumeration`: listing the stored credentials (see `search` and
+  `enumerate_with_metadata`).
+- `custom_attributes`: storing attributes of the application's choosing
+  with a credential (Linux items have any attributes; Windows and Mac
+  credentials have only their fixed fields).
+- `multiple_stores`: keeping credentials in a store other than the
+  default (a Linux collection, or a Mac keychain domain).
+- `creating_app`: knowing which application created a credential (see
+  `Entry::creating_app`).
+- `trusted_applications`: knowing which applications may read a
+  credential without a prompt (see `Entry::access_control`).
+- `unlimited_passwords`: storing passwords of any length (Windows
+  limits them, see `Entry::remaining_capacity`).
+ */
+
+use crate::{platform, Error, Platform, Result};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub enumeration: bool,
+    pub custom_attributes: bool,
+    pub multiple_stores: bool,
+    pub creating_app: bool,
+    pub trusted_applications: bool,
+    pub unlimited_passwords: bool,
+}
+
+impl Capabilities {
+    // The names of the capabilities in this set, in declaration order.
+    fn names(&self) -> Vec<&'static str> {
+        let all = [
+            (self.enumeration, "enumeration"),
+            (self.custom_attributes, "custom attributes"),
+            (self.multiple_stores, "multiple stores"),
+            (self.creating_app, "creating application"),
+            (self.trusted_applications, "trusted applications"),
+            (self.unlimited_passwords, "unlimited passwords"),
+        ];
+        all.iter()
+            .filter(|(present, _)| *present)
+            .map(|(_, name)| *name)
+            .collect()
+    }
+}
+
+// The capabilities of the platform's secure storage.
+pub fn capabilities() -> Capabilities {
+    let this = crate::platform();
+    let not_windows = !matches!(this, Platform::Windows);
+    Capabilities {
+        enumeration: true,
+        custom_attributes: matches!(this, Platform::Linux),
+        multiple_stores: not_windows,
+        creating_app: not_windows,
+        trusted_applications: matches!(this, Platform::MacOs),
+        unlimited_passwords: platform::password_capacity().is_none(),
+    }
+}
+
+// Check that the platform's secure storage has each of the required
+// capabilities, failing with `Unsupported` (naming the ones it lacks)
+// if it doesn't.
+pub fn require_capabilities(required: &Capabilities) -> Result<()> {
+    let available = capabilities();
+    let missing = Capabilities {
+        enumeration: required.enumeration && !available.enumeration,
+        custom_attributes: required.custom_attributes && !available.custom_attributes,
+        multiple_stores: required.multiple_stores && !available.multiple_stores,
+        creating_app: required.creating_app && !available.creating_app,
+        trusted_applications: required.trusted_applications && !available.trusted_applications,
+        unlimited_passwords: required.unlimited_passwords && !available.unlimited_passwords,
+    };
+    match missing.names() {
+        names if names.is_empty() => Ok(()),
+        names => Err(Error::Unsupported(names)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        let set = Capabilities {
+            custom_attributes: true,
+            unlimited_passwords: true,
+            ..Capabilities::default()
+        };
+        assert_eq!(set.names(), ["custom attributes", "unlimited passwords"]);
+        assert!(Capabilities::default().names().is_empty());
+    }
+
+    #[test]
+    fn test_require_capabilities() {
+        let available = capabilities();
+        assert!(require_capabilities(&available).is_ok());
+        assert!(require_capabilities(&Capabilities::default()).is_ok());
+        let everything = Capabilities {
+            enumeration: true,
+            custom_attributes: true,
+            multiple_stores: true,
+            creating_app: true,
+            trusted_applications: true,
+            unlimited_passwords: true,
+        };
+        // no platform has all of them
+        match require_capabilities(&everything) {
+            Err(Error::Unsupported(missing)) => {
+                assert!(!missing.is_empty());
+                assert!(!missing.contains(&"enumeration"));
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
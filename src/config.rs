@@ -0,0 +1,608 @@
+/*
+Configuration of the crate's behavior.
+
+Configuration can be set at two scopes.  The global configuration
+applies to every entry in the process.  An entry can also be created
+with its own configuration (see `Entry::new_with_config`), and any
+setting made in an entry's configuration overrides the global one for
+that entry.  This lets independent components of a larger application
+each configure keyring for themselves, without clobbering each other's
+(or the application's) settings.
+
+Hooks are called without any internal lock held, so a hook may itself
+use the crate (or even replace the hook) without deadlocking.
+ */
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "cache-file")]
+use crate::cache_file::CacheFile;
+use crate::credential::PlatformCredential;
+use crate::error::PolicyViolation;
+use crate::serialized::{hex, unhex};
+use crate::Error;
+
+type Clock = dyn Fn() -> SystemTime + Send + Sync;
+type ConfirmHook = dyn Fn(&PlatformCredential) -> bool + Send + Sync;
+type NameHasher = dyn Fn(&str) -> String + Send + Sync;
+type Provisioner = dyn Fn(&PlatformCredential) -> Option<String> + Send + Sync;
+type Policy = dyn Fn(&[u8]) -> Result<(), PolicyViolation> + Send + Sync;
+
+// A transformation of passwords on their way to and from secure
+// storage, such as envelope encryption under an application-managed key.
+// `on_store` is given each password (after normalization and the policy
+// check) before it's stored, and `on_load` is given what `on_store`
+// returned, when it's read back, and must return the password again.
+// What `on_store` returns is stored in hex, so it can be any bytes.  An
+// error from either is returned as the error of the set or get.
+pub trait SecretTransform: Send + Sync {
+    fn on_store(&self, password: &[u8]) -> crate::Result<Vec<u8>>;
+    fn on_load(&self, stored: &[u8]) -> crate::Result<Vec<u8>>;
+}
+
+// How passwords are normalized before they are stored, and after they
+// are read back, and when they are compared.  Because normalization is
+// applied on every path, a password round-trips consistently whichever
+// way it was written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    // Passwords are used exactly as given (the default).
+    Exact,
+    // Trailing whitespace (including line breaks) is removed, so that a
+    // password written by a shell tool with a trailing newline matches the
+    // same password entered without one.
+    TrimTrailingWhitespace,
+}
+
+// What happens when a password is stored in a Linux collection that
+// doesn't exist.  (On other platforms, stores always exist.)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CollectionPolicy {
+    // Storing the password fails with `NoSuchCollection` (the default).
+    // The password is never stored in some other collection instead.
+    RequireExisting,
+    // The collection is created, with the collection's name as its alias
+    // and label, and the password stored in it.  The secret service
+    // prompts the user for the new collection's password.
+    CreateIfMissing,
+}
+
+// A set of configuration settings.  Settings that have not been made
+// are inherited: an entry's unset settings come from the global
+// configuration, and unset global settings have their default behavior.
+#[derive(Clone, Default)]
+pub struct Config {
+    store: Option<String>,
+    normalization: Option<Normalization>,
+    collection_policy: Option<CollectionPolicy>,
+    confirm_hook: Option<Arc<ConfirmHook>>,
+    policy: Option<Arc<Policy>>,
+    provisioner: Option<Arc<Provisioner>>,
+    transform: Option<Arc<dyn SecretTransform>>,
+    name_hasher: Option<Arc<NameHasher>>,
+    absence_ttl: Option<Duration>,
+    case_insensitive_usernames: Option<bool>,
+    prompt_interval: Option<Duration>,
+    transient_retries: Option<(u32, Duration)>,
+    enforced_schedules: Option<bool>,
+    encoding_markers: Option<bool>,
+    empty_as_corrupt: Option<bool>,
+    reset_attributes: Option<bool>,
+    clock: Option<Arc<Clock>>,
+    #[cfg(feature = "cache-file")]
+    cache_file: Option<Arc<CacheFile>>,
+}
+
+static GLOBAL: RwLock<Config> = RwLock::new(Config {
+    store: None,
+    normalization: None,
+    collection_policy: None,
+    confirm_hook: None,
+    policy: None,
+    provisioner: None,
+    transform: None,
+    name_hasher: None,
+    absence_ttl: None,
+    case_insensitive_usernames: None,
+    prompt_interval: None,
+    transient_retries: None,
+    enforced_schedules: None,
+    encoding_markers: None,
+    empty_as_corrupt: None,
+    reset_attributes: None,
+    clock: None,
+    #[cfg(feature = "cache-file")]
+    cache_file: None,
+});
+
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("store", &self.store)
+            .field("normalization", &self.normalization)
+            .field("collection_policy", &self.collection_policy)
+            .field("confirm_hook", &self.confirm_hook.is_some())
+            .field("policy", &self.policy.is_some())
+            .field("provisioner", &self.provisioner.is_some())
+            .field("transform", &self.transform.is_some())
+            .field("name_hasher", &self.name_hasher.is_some())
+            .field("absence_ttl", &self.absence_ttl)
+            .field(
+                "case_insensitive_usernames",
+                &self.case_insensitive_usernames,
+            )
+            .field("prompt_interval", &self.prompt_interval)
+            .field("transient_retries", &self.transient_retries)
+            .field("enforced_schedules", &self.enforced_schedules)
+            .field("encoding_markers", &self.encoding_markers)
+            .field("empty_as_corrupt", &self.empty_as_corrupt)
+            .field("reset_attributes", &self.reset_attributes)
+            .field("clock", &self.clock.is_some())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Config {
+    // A configuration with no settings made.
+    pub fn new() -> Config {
+        Config::default()
+    }
+
+    // Select the store that entries are created in when they have no
+    // explicit target: the collection on Linux, or the keychain domain on
+    // Mac.  Windows has only one store, so there this setting is ignored.
+    pub fn with_store(mut self, store: &str) -> Config {
+        self.store = Some(store.to_string());
+        self
+    }
+
+    // Select how passwords are normalized.
+    pub fn with_normalization(mut self, normalization: Normalization) -> Config {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    // Select what happens when a password is stored in a Linux
+    // collection that doesn't exist.
+    pub fn with_collection_policy(mut self, policy: CollectionPolicy) -> Config {
+        self.collection_policy = Some(policy);
+        self
+    }
+
+    // Set the hook that is asked to confirm each deletion of a credential
+    // from secure storage.  The hook receives the credential about to be
+    // deleted (never its password), and the deletion only happens if the
+    // hook returns true; otherwise the deletion fails with a `Cancelled`
+    // error.
+    pub fn with_confirm_hook<F>(mut self, hook: F) -> Config
+    where
+        F: Fn(&PlatformCredential) -> bool + Send + Sync + 'static,
+    {
+        self.confirm_hook = Some(Arc::new(hook));
+        self
+    }
+
+    // Set the policy that every password must satisfy before it is stored
+    // in secure storage.  The policy receives the password (after
+    // normalization) and can reject it with the reason why, in which case
+    // the password isn't stored and setting it fails with a
+    // `PolicyViolation` error.  The policy mustn't log or keep the password.
+    pub fn with_policy<F>(mut self, policy: F) -> Config
+    where
+        F: Fn(&[u8]) -> Result<(), PolicyViolation> + Send + Sync + 'static,
+    {
+        self.policy = Some(Arc::new(policy));
+        self
+    }
+
+    // Set the provisioner that supplies passwords for credentials that
+    // aren't found: when reading a password fails with `NoEntry`, the
+    // provisioner is given the credential (there's no password to give
+    // it), and if it returns a password, that password is stored and
+    // returned instead.  If it returns `None`, the read fails as before.
+    pub fn with_provisioner<F>(mut self, provisioner: F) -> Config
+    where
+        F: Fn(&PlatformCredential) -> Option<String> + Send + Sync + 'static,
+    {
+        self.provisioner = Some(Arc::new(provisioner));
+        self
+    }
+
+    // Set the transform applied to passwords on their way to and from
+    // secure storage (see `SecretTransform`).  Passwords stored without
+    // the transform can't be read with it, and vice versa, so it should
+    // be set before any passwords are stored.
+    pub fn with_transform<T>(mut self, transform: T) -> Config
+    where
+        T: SecretTransform + 'static,
+    {
+        self.transform = Some(Arc::new(transform));
+        self
+    }
+
+    // Set a function that entries created with this configuration (see
+    // `Entry::new_with_config`) pass their service and username through
+    // before mapping them to a credential, so that the names stored in
+    // secure storage don't reveal which services the user has accounts
+    // with.  The function should be a salted cryptographic hash (or a
+    // keyed MAC), and must always give the same result for the same name,
+    // or entries won't find their credentials again.  Credentials listed by
+    // `search` and reported by `inventory` will have the hashed names.
+    pub fn with_name_hasher<F>(mut self, hasher: F) -> Config
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        self.name_hasher = Some(Arc::new(hasher));
+        self
+    }
+
+    // Remember for the given time that a credential wasn't found, so that
+    // reading its password again in the meantime fails with `NoEntry`
+    // without going to secure storage.  Setting the password forgets
+    // that the credential was absent.
+    pub fn with_absence_ttl(mut self, ttl: Duration) -> Config {
+        self.absence_ttl = Some(ttl);
+        self
+    }
+
+    // Select whether a credential that isn't found under the entry's
+    // username is looked for again, ignoring the case of the username.
+    // On Linux and Mac this looks through the credentials in the default
+    // collection or User keychain (see `search`).  Windows already ignores
+    // case when looking up target names, so there this setting is ignored.
+    // Passwords are always set under the username as given.
+    pub fn with_case_insensitive_usernames(mut self, enabled: bool) -> Config {
+        self.case_insensitive_usernames = Some(enabled);
+        self
+    }
+
+    // Limit how often the platform may prompt the user on behalf of entries
+    // with this configuration: an operation that would prompt within the
+    // given interval of the last prompt fails with `NoInteraction` instead.
+    // Only Mac prompts can be limited.
+    pub fn with_prompt_interval(mut self, interval: Duration) -> Config {
+        self.prompt_interval = Some(interval);
+        self
+    }
+
+    // Retry operations that fail with a transient error (see
+    // `Error::is_transient`) up to the given number of times, waiting the
+    // given delay between attempts.  For example, services can get
+    // `NoStorageAccess` while their logon session is being set up, and
+    // storage is inaccessible while it's locked.  Other errors are never
+    // retried.
+    pub fn with_transient_retries(mut self, retries: u32, delay: Duration) -> Config {
+        self.transient_retries = Some((retries, delay));
+        self
+    }
+
+    // Select whether entries with this configuration enforce the access
+    // schedules stored with passwords (see
+    // `Entry::set_password_with_schedule`), failing to read a password
+    // outside its schedule.  This costs an extra read of secure storage
+    // for each read of a password.
+    pub fn with_enforced_schedules(mut self, enabled: bool) -> Config {
+        self.enforced_schedules = Some(enabled);
+        self
+    }
+
+    // Select whether entries with this configuration use the encoding
+    // markers stored with passwords (see `Entry::set_password_encoded`):
+    // reading a password with a marker decodes it in the marked encoding,
+    // and setting a password in the platform's encoding, or deleting it,
+    // removes the marker.  This costs an extra access of secure storage
+    // for each read and write.
+    pub fn with_encoding_markers(mut self, enabled: bool) -> Config {
+        self.encoding_markers = Some(enabled);
+        self
+    }
+
+    // Select whether entries with this configuration take a credential
+    // with an empty stored password to be corrupt, so that reading it
+    // fails with `CorruptCredential`, instead of returning the empty
+    // string (the default).  Set it in the global configuration for a
+    // single policy across the application.  Either way, a missing
+    // credential is `NoEntry`.
+    pub fn with_empty_as_corrupt(mut self, enabled: bool) -> Config {
+        self.empty_as_corrupt = Some(enabled);
+        self
+    }
+
+    // Select whether setting the password of an existing credential
+    // resets the attributes that the entry's credential doesn't determine
+    // (see `Entry::set_password`) instead of preserving them.  Mac items
+    // keep the attributes a credential leaves unspecified either way.
+    pub fn with_reset_attributes(mut self, enabled: bool) -> Config {
+        self.reset_attributes = Some(enabled);
+        self
+    }
+
+    // Use the given clock, instead of the system clock, to tell the time
+    // when enforcing access schedules.  This is mostly useful in tests.
+    pub fn with_clock<F>(mut self, clock: F) -> Config
+    where
+        F: Fn() -> SystemTime + Send + Sync + 'static,
+    {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    // Keep the passwords read (by entries with this configuration) in
+    // the given cache file, and read them from there while they are fresh.
+    #[cfg(feature = "cache-file")]
+    pub fn with_cache_file(mut self, cache_file: Arc<CacheFile>) -> Config {
+        self.cache_file = Some(cache_file);
+        self
+    }
+
+    // The store to use for an entry with this configuration.
+    pub(crate) fn store(&self) -> Option<String> {
+        self.store.clone().or_else(|| global().store)
+    }
+
+    // The collection policy of an entry with this configuration.
+    pub(crate) fn collection_policy(&self) -> CollectionPolicy {
+        self.collection_policy
+            .or_else(|| global().collection_policy)
+            .unwrap_or(CollectionPolicy::RequireExisting)
+    }
+
+    // Check a password against the policy for an entry with this
+    // configuration, if there is one.
+    pub(crate) fn check_policy(&self, password: &str) -> Result<(), PolicyViolation> {
+        match self.policy.clone().or_else(|| global().policy) {
+            Some(policy) => policy(password.as_bytes()),
+            None => Ok(()),
+        }
+    }
+
+    // The form in which a password is stored by an entry with this
+    // configuration: transformed, if there is a transform.
+    pub(crate) fn transform_store(&self, password: &str) -> crate::Result<String> {
+        match self.transform.clone().or_else(|| global().transform) {
+            Some(transform) => Ok(hex(&transform.on_store(password.as_bytes())?)),
+            None => Ok(password.to_string()),
+        }
+    }
+
+    // The password stored in the given form by an entry with this
+    // configuration.
+    pub(crate) fn transform_load(&self, stored: String) -> crate::Result<String> {
+        match self.transform.clone().or_else(|| global().transform) {
+            Some(transform) => {
+                let bytes = unhex(&stored)
+                    .ok_or_else(|| Error::BadFormat("transformed password".to_string()))?;
+                let password = transform.on_load(&bytes)?;
+                String::from_utf8(password).map_err(|err| Error::BadEncoding(err.into_bytes()))
+            }
+            None => Ok(stored),
+        }
+    }
+
+    // The name stored for a service or username given to an entry with
+    // this configuration.
+    pub(crate) fn hash_name(&self, name: &str) -> String {
+        match self.name_hasher.clone().or_else(|| global().name_hasher) {
+            Some(hasher) => hasher(name),
+            None => name.to_string(),
+        }
+    }
+
+    // Normalize a password for an entry with this configuration.
+    pub(crate) fn normalize<'a>(&self, password: &'a str) -> &'a str {
+        let normalization = self.normalization.or_else(|| global().normalization);
+        match normalization.unwrap_or(Normalization::Exact) {
+            Normalization::Exact => password,
+            Normalization::TrimTrailingWhitespace => password.trim_end(),
+        }
+    }
+
+    // Normalize a password read back for an entry with this configuration,
+    // in place (so the password isn't copied).
+    pub(crate) fn normalize_read(&self, mut password: String) -> String {
+        let normalized = self.normalize(&password).len();
+        password.truncate(normalized);
+        password
+    }
+
+    // Ask the confirmation hook for an entry with this configuration,
+    // if there is one, whether to delete the given credential.
+    pub(crate) fn confirm_delete(&self, target: &PlatformCredential) -> bool {
+        match self.confirm_hook.clone().or_else(|| global().confirm_hook) {
+            Some(hook) => hook(target),
+            None => true,
+        }
+    }
+
+    // Ask the provisioner for an entry with this configuration, if there
+    // is one, for a password for the given (missing) credential.
+    pub(crate) fn provision(&self, target: &PlatformCredential) -> Option<String> {
+        let provisioner = self.provisioner.clone().or_else(|| global().provisioner)?;
+        provisioner(target)
+    }
+
+    // How long an entry with this configuration remembers that its
+    // credential is absent, if it does.
+    pub(crate) fn absence_ttl(&self) -> Option<Duration> {
+        self.absence_ttl.or_else(|| global().absence_ttl)
+    }
+
+    // Whether an entry with this configuration looks for its credential
+    // again ignoring the case of its username.
+    pub(crate) fn case_insensitive_usernames(&self) -> bool {
+        self.case_insensitive_usernames
+            .or_else(|| global().case_insensitive_usernames)
+            .unwrap_or(false)
+    }
+
+    // The prompt interval of an entry with this configuration, if it has one.
+    pub(crate) fn prompt_interval(&self) -> Option<Duration> {
+        self.prompt_interval.or_else(|| global().prompt_interval)
+    }
+
+    // How many times, and how often, an entry with this configuration
+    // retries an operation that fails with a transient error.
+    pub(crate) fn transient_retries(&self) -> (u32, Duration) {
+        self.transient_retries
+            .or_else(|| global().transient_retries)
+            .unwrap_or((0, Duration::ZERO))
+    }
+
+    // Whether an entry with this configuration enforces access schedules.
+    pub(crate) fn enforced_schedules(&self) -> bool {
+        self.enforced_schedules
+            .or_else(|| global().enforced_schedules)
+            .unwrap_or(false)
+    }
+
+    // Whether an entry with this configuration uses encoding markers.
+    pub(crate) fn encoding_markers(&self) -> bool {
+        self.encoding_markers
+            .or_else(|| global().encoding_markers)
+            .unwrap_or(false)
+    }
+
+    // Whether an entry with this configuration takes an empty stored
+    // password to be corrupt.
+    pub(crate) fn empty_as_corrupt(&self) -> bool {
+        self.empty_as_corrupt
+            .or_else(|| global().empty_as_corrupt)
+            .unwrap_or(false)
+    }
+
+    // Whether an entry with this configuration resets the attributes of
+    // an existing credential when setting its password.
+    pub(crate) fn reset_attributes(&self) -> bool {
+        self.reset_attributes
+            .or_else(|| global().reset_attributes)
+            .unwrap_or(false)
+    }
+
+    // The time now, according to the clock of an entry with this
+    // configuration.
+    pub(crate) fn now(&self) -> SystemTime {
+        match self.clock.clone().or_else(|| global().clock) {
+            Some(clock) => clock(),
+            None => SystemTime::now(),
+        }
+    }
+
+    // The cache file used by an entry with this configuration.
+    #[cfg(feature = "cache-file")]
+    pub(crate) fn cache_file(&self) -> Option<Arc<CacheFile>> {
+        self.cache_file.clone().or_else(|| global().cache_file)
+    }
+}
+
+// Replace the global configuration.
+pub fn set_global_config(config: Config) {
+    *GLOBAL.write().unwrap_or_else(|e| e.into_inner()) = config;
+}
+
+// A copy of the current global configuration.
+pub fn global_config() -> Config {
+    global()
+}
+
+// Register a global confirmation hook (see `Config::with_confirm_hook`),
+// replacing any previously registered global hook.
+pub fn set_confirm_hook<F>(hook: F)
+where
+    F: Fn(&PlatformCredential) -> bool + Send + Sync + 'static,
+{
+    GLOBAL
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .confirm_hook = Some(Arc::new(hook));
+}
+
+// Remove the global confirmation hook, so deletions are confirmed unless
+// an entry has its own hook.
+pub fn clear_confirm_hook() {
+    GLOBAL
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .confirm_hook = None;
+}
+
+// Register a global password policy (see `Config::with_policy`),
+// replacing any previously registered global policy.
+pub fn set_policy<F>(policy: F)
+where
+    F: Fn(&[u8]) -> Result<(), PolicyViolation> + Send + Sync + 'static,
+{
+    GLOBAL.write().unwrap_or_else(|e| e.into_inner()).policy = Some(Arc::new(policy));
+}
+
+// Remove the global password policy, so passwords are stored unchecked
+// unless an entry has its own policy.
+pub fn clear_policy() {
+    GLOBAL.write().unwrap_or_else(|e| e.into_inner()).policy = None;
+}
+
+// Register a global provisioner (see `Config::with_provisioner`),
+// replacing any previously registered global provisioner.
+pub fn set_provisioner<F>(provisioner: F)
+where
+    F: Fn(&PlatformCredential) -> Option<String> + Send + Sync + 'static,
+{
+    GLOBAL
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .provisioner = Some(Arc::new(provisioner));
+}
+
+// Remove the global provisioner, so missing passwords stay missing
+// unless an entry has its own provisioner.
+pub fn clear_provisioner() {
+    GLOBAL
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .provisioner = None;
+}
+
+// Register a global transform (see `Config::with_transform`), replacing
+// any previously registered global transform.
+pub fn set_transform<T>(transform: T)
+where
+    T: SecretTransform + 'static,
+{
+    GLOBAL.write().unwrap_or_else(|e| e.into_inner()).transform = Some(Arc::new(transform));
+}
+
+// Remove the global transform, so passwords are stored as they are
+// unless an entry has its own transform.
+pub fn clear_transform() {
+    GLOBAL.write().unwrap_or_else(|e| e.into_inner()).transform = None;
+}
+
+fn global() -> Config {
+    GLOBAL.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credential::{default_target, Platform};
+
+    #[test]
+    fn test_entry_settings_override_global() {
+        let target = default_target(&Platform::Linux, None, "service", "user");
+        let refuse = Config::new()
+            .with_store("entry")
+            .with_confirm_hook(|_| false);
+        assert_eq!(refuse.store().as_deref(), Some("entry"));
+        assert!(!refuse.confirm_delete(&target));
+        let trim = Config::new().with_normalization(Normalization::TrimTrailingWhitespace);
+        assert_eq!(trim.normalize("secret \r\n"), "secret");
+        let exact = Config::new().with_normalization(Normalization::Exact);
+        assert_eq!(exact.normalize("secret \r\n"), "secret \r\n");
+        // the global configuration is shared with other tests, so
+        // only check that an unset entry doesn't override it
+        let unset = Config::new();
+        assert_eq!(unset.store(), global().store);
+        assert!(unset.confirm_hook.is_none());
+    }
+}
@@ -0,0 +1,43 @@
+/*
+A password can be stored with a use context (see
+`Entry::set_password_with_context`): a short name, such as `prod-db`, for
+what it's meant to be used for.  Reading the password for a context (see
+`Entry::get_password_for_context`) fails with `ContextMismatch` if it was
+stored for a different one, which catches code that reads, say, the
+staging credential on a production path.
+
+The context is checked by the crate, so it's a guard against
+misconfiguration, not an access control: reading the password any other
+way ignores it.  A password stored without a context is read for any
+context.
+
+The context name is kept in the credential's context companion.
+Setting the password again without a context removes the context, as
+does deleting the password (through any entry), so a context never
+outlives the password it was stored with.
+ */
+
+use crate::credential::PlatformCredential;
+use crate::{platform, Error, Result};
+
+pub(crate) const PURPOSE: &str = "context";
+
+pub(crate) fn read(target: &PlatformCredential) -> Result<Option<String>> {
+    match platform::get_password(&mut target.companion(PURPOSE)) {
+        Ok(context) => Ok(Some(context)),
+        Err(Error::NoEntry) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+pub(crate) fn write(target: &PlatformCredential, context: &str) -> Result<()> {
+    platform::set_password(&target.companion(PURPOSE), context)
+}
+
+// Remove the context of a credential, if it has one.
+pub(crate) fn delete(target: &PlatformCredential) -> Result<()> {
+    match platform::delete_password(&target.companion(PURPOSE)) {
+        Ok(()) | Err(Error::NoEntry) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
@@ -34,6 +34,9 @@ platform-specific credential mapping.  Or read the code here :).
  */
 
 use std::collections::HashMap;
+use std::time::SystemTime;
+
+use crate::timestamp;
 
 #[derive(Debug)]
 pub enum Platform {
@@ -79,13 +82,18 @@ pub struct WinCredential {
 
 // MacOS supports multiple OS-provided credential stores, and used to support creating
 // arbitrary new credential stores (but that has been deprecated).  Credentials on
-// Mac also can have "type" but we don't reflect that here because the type is actually
-// opaque once set and is only used in the Keychain UI.
+// Mac can also have "creator" and "type" four-character codes, which legacy apps
+// use to mark the items they own, and a comment shown in _Keychain Access_.  These
+// are read whenever a credential is retrieved, and are set on the item when the
+// credential specifies them.
 #[derive(Debug, Clone, PartialEq)]
 pub struct MacCredential {
     pub domain: MacKeychainDomain,
     pub service: String,
     pub account: String,
+    pub creator_code: Option<[u8; 4]>,
+    pub type_code: Option<[u8; 4]>,
+    pub comment: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -116,6 +124,65 @@ impl From<Option<&str>> for MacKeychainDomain {
     }
 }
 
+// Windows credentials are persisted for different lengths of time:
+// only for the current logon session, on the local machine across
+// sessions, or across all machines the user logs on to (that is,
+// roaming with the user's profile).  This crate writes credentials
+// with `Enterprise` persistence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WinPersistence {
+    Session,
+    LocalMachine,
+    Enterprise,
+}
+
+// The metadata that the platform keeps about a stored credential.
+// Fields that the platform doesn't keep are always `None`: Windows
+// doesn't record when a credential was created, only Windows has
+// a notion of persistence, and Linux has nowhere to show an expiry
+// (see `Entry::set_password_with_expiry`).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CredentialMetadata {
+    pub created: Option<SystemTime>,
+    pub modified: Option<SystemTime>,
+    pub persistence: Option<WinPersistence>,
+    pub expires: Option<SystemTime>,
+}
+
+// When a Mac keychain item can be read: whenever the device is unlocked,
+// after the first unlock since boot, or always.  The `ThisDeviceOnly`
+// levels also keep the item from migrating to other devices.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MacAccessibility {
+    WhenUnlocked,
+    AfterFirstUnlock,
+    Always,
+    WhenPasscodeSetThisDeviceOnly,
+    WhenUnlockedThisDeviceOnly,
+    AfterFirstUnlockThisDeviceOnly,
+    AlwaysThisDeviceOnly,
+}
+
+// The security-relevant settings that control access to a stored
+// credential, as far as each platform has them.  Fields that don't
+// apply to the platform are always `None`:
+// - `accessibility` and `synchronizable` are Mac keychain item settings.
+//   An item in a keychain without accessibility levels (such as the
+//   login keychain) has no accessibility.
+// - `trusted_applications` are the paths of the applications a Mac
+//   item's access control lists allow to read it without prompting.
+// - `persistence` is the Windows persistence of the credential.
+// - `locked` is whether the Linux collection holding the credential is
+//   locked.  Reading the password unlocks it (which may prompt the user).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccessControl {
+    pub accessibility: Option<MacAccessibility>,
+    pub synchronizable: Option<bool>,
+    pub trusted_applications: Option<Vec<String>>,
+    pub persistence: Option<WinPersistence>,
+    pub locked: Option<bool>,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum PlatformCredential {
     Linux(LinuxCredential),
@@ -131,8 +198,315 @@ impl PlatformCredential {
             PlatformCredential::Win(_) => matches!(os, Platform::Windows),
         }
     }
+
+    // The identifier of the secure storage backend that holds this
+    // credential: "secret-service", "windows-credential-manager" or
+    // "macos-keychain".
+    pub fn backend_name(&self) -> &'static str {
+        match self {
+            PlatformCredential::Linux(_) => "secret-service",
+            PlatformCredential::Win(_) => "windows-credential-manager",
+            PlatformCredential::Mac(_) => "macos-keychain",
+        }
+    }
+
+    // The service this credential was created for.  On Linux and Mac this
+    // is recorded directly in the credential.  On Windows it's recovered
+    // from the target name by removing the `username.` prefix (if it has
+    // one) that the default mapping adds.
+    pub fn service(&self) -> &str {
+        match self {
+            PlatformCredential::Linux(cred) => cred
+                .attributes
+                .get("service")
+                .map(String::as_str)
+                .unwrap_or_default(),
+            PlatformCredential::Win(cred) => cred
+                .target_name
+                .strip_prefix(cred.username.as_str())
+                .and_then(|rest| rest.strip_prefix('.'))
+                .unwrap_or(&cred.target_name),
+            PlatformCredential::Mac(cred) => &cred.service,
+        }
+    }
+
+    // The username this credential was created for.
+    pub fn username(&self) -> &str {
+        match self {
+            PlatformCredential::Linux(cred) => cred
+                .attributes
+                .get("username")
+                .map(String::as_str)
+                .unwrap_or_default(),
+            PlatformCredential::Win(cred) => &cred.username,
+            PlatformCredential::Mac(cred) => &cred.account,
+        }
+    }
+
+    // The same credential for a different username.  Only the parts that
+    // depend on the username are changed: the username itself, the `username.`
+    // prefix of a Windows target name (if it has one), and the username in
+    // the default label or comment (if it has one).  So for a credential
+    // produced by the default mapper, this is the credential the mapper
+    // would have produced for the new username.
+    pub(crate) fn with_username(&self, username: &str) -> PlatformCredential {
+        let old = self.username().to_string();
+        let relabel = |label: &str| match label.strip_suffix(&format!("user '{}'", old)) {
+            Some(prefix) => format!("{}user '{}'", prefix, username),
+            None => label.to_string(),
+        };
+        match self {
+            PlatformCredential::Linux(cred) => {
+                let mut attributes = cred.attributes.clone();
+                attributes.insert("username".to_string(), username.to_string());
+                PlatformCredential::Linux(LinuxCredential {
+                    collection: cred.collection.clone(),
+                    attributes,
+                    label: relabel(&cred.label),
+                })
+            }
+            PlatformCredential::Win(cred) => PlatformCredential::Win(WinCredential {
+                username: username.to_string(),
+                target_name: match cred.target_name.strip_prefix(&format!("{}.", old)) {
+                    Some(service) => format!("{}.{}", username, service),
+                    None => cred.target_name.clone(),
+                },
+                target_alias: cred.target_alias.clone(),
+                comment: relabel(&cred.comment),
+            }),
+            PlatformCredential::Mac(cred) => PlatformCredential::Mac(MacCredential {
+                account: username.to_string(),
+                ..cred.clone()
+            }),
+        }
+    }
+
+    // Whether the other credential is the same as this one but for the
+    // case of its username.  Windows target names are compared without
+    // regard to case by the platform itself, so there this is never true.
+    pub(crate) fn matches_ignoring_username_case(&self, other: &PlatformCredential) -> bool {
+        let same_username = self.username().to_lowercase() == other.username().to_lowercase();
+        match (self, other) {
+            (PlatformCredential::Linux(cred), PlatformCredential::Linux(other)) => {
+                cred.collection == other.collection
+                    && cred
+                        .attributes
+                        .iter()
+                        .filter(|(key, _)| key.as_str() != "username")
+                        .all(|(key, value)| other.attributes.get(key) == Some(value))
+                    && same_username
+            }
+            (PlatformCredential::Mac(cred), PlatformCredential::Mac(other)) => {
+                cred.domain == other.domain && cred.service == other.service && same_username
+            }
+            _ => false,
+        }
+    }
+
+    // Whether this credential is in the platform's default store, that
+    // is, whether it would be listed by `search` if it exists.
+    pub(crate) fn in_default_store(&self) -> bool {
+        match self {
+            PlatformCredential::Linux(cred) => cred.collection == "default",
+            PlatformCredential::Win(_) => true,
+            PlatformCredential::Mac(cred) => cred.domain == MacKeychainDomain::User,
+        }
+    }
+
+    // Whether the listed credential is this one: the one the platform
+    // would find when looking this credential up.  Secret service lookups
+    // match any item having the searched-for attributes, and Windows
+    // target names are compared without regard to case.
+    pub(crate) fn is_found_as(&self, listed: &PlatformCredential) -> bool {
+        match (self, listed) {
+            (PlatformCredential::Linux(cred), PlatformCredential::Linux(listed)) => {
+                cred.collection == listed.collection
+                    && cred
+                        .attributes
+                        .iter()
+                        .all(|(key, value)| listed.attributes.get(key) == Some(value))
+            }
+            (PlatformCredential::Win(cred), PlatformCredential::Win(listed)) => {
+                cred.target_name.to_lowercase() == listed.target_name.to_lowercase()
+            }
+            (PlatformCredential::Mac(cred), PlatformCredential::Mac(listed)) => {
+                cred.domain == listed.domain
+                    && cred.service == listed.service
+                    && cred.account == listed.account
+            }
+            _ => false,
+        }
+    }
+
+    // Whether this is a companion credential (see below).  Companions
+    // are managed by this crate, so they are never listed as credentials
+    // in their own right.
+    pub(crate) fn is_companion(&self) -> bool {
+        match self {
+            PlatformCredential::Linux(cred) => cred.attributes.contains_key(COMPANION_ATTRIBUTE),
+            PlatformCredential::Win(cred) => cred.target_name.starts_with(COMPANION_PREFIX),
+            PlatformCredential::Mac(cred) => cred.service.starts_with(COMPANION_PREFIX),
+        }
+    }
+
+    // Derive the credential used to hold data this crate keeps about
+    // this one (such as an access log), one for each purpose.  A
+    // companion is stored next to the credential it describes, in the
+    // same secure storage, so any process that can read the credential
+    // can read its companions too.  It is identified so that it never
+    // matches the credential's own lookups (and vice versa), and it is
+    // never listed by `search` and friends.  Companions hold only the
+    // crate's data about a credential, never its password.
+    pub(crate) fn companion(&self, purpose: &str) -> PlatformCredential {
+        match self {
+            PlatformCredential::Linux(cred) => {
+                // Secret service lookups match any item having the searched-for
+                // attributes, so the companion can't share any attributes with
+                // its owner.  Instead it records the owner's attributes in a
+                // single canonical value.
+                let mut owner: Vec<String> = cred
+                    .attributes
+                    .iter()
+                    .map(|(k, v)| format!("{}={}", k, v))
+                    .collect();
+                owner.sort();
+                PlatformCredential::Linux(LinuxCredential {
+                    collection: cred.collection.clone(),
+                    attributes: HashMap::from([
+                        (COMPANION_ATTRIBUTE.to_string(), purpose.to_string()),
+                        ("keyring-rs:owner".to_string(), owner.join("&")),
+                    ]),
+                    label: format!("keyring-rs {} for: {}", purpose, cred.label),
+                })
+            }
+            PlatformCredential::Win(cred) => PlatformCredential::Win(WinCredential {
+                username: cred.username.clone(),
+                target_name: format!("{}{}:{}", COMPANION_PREFIX, purpose, cred.target_name),
+                target_alias: String::new(),
+                comment: format!("keyring-rs {} for: {}", purpose, cred.target_name),
+            }),
+            PlatformCredential::Mac(cred) => PlatformCredential::Mac(MacCredential {
+                domain: cred.domain.clone(),
+                service: format!("{}{}:{}", COMPANION_PREFIX, purpose, cred.service),
+                account: cred.account.clone(),
+                creator_code: None,
+                type_code: None,
+                comment: None,
+            }),
+        }
+    }
+
+    // The same credential, but keeping the attributes that the given
+    // stored version of it has and this one doesn't determine: the label
+    // and any extra attributes of a Linux item (this credential's own
+    // attributes win), and the comment and target alias of a Windows
+    // credential.  A Mac item only has its attributes changed when a
+    // credential specifies them, so this takes whichever of its codes and
+    // comment this credential leaves unspecified from the stored version.
+    pub(crate) fn preserving(&self, stored: &PlatformCredential) -> PlatformCredential {
+        match (self, stored) {
+            (PlatformCredential::Linux(cred), PlatformCredential::Linux(stored)) => {
+                let mut attributes = stored.attributes.clone();
+                attributes.extend(cred.attributes.clone());
+                PlatformCredential::Linux(LinuxCredential {
+                    collection: cred.collection.clone(),
+                    attributes,
+                    label: stored.label.clone(),
+                })
+            }
+            (PlatformCredential::Win(cred), PlatformCredential::Win(stored)) => {
+                PlatformCredential::Win(WinCredential {
+                    target_alias: stored.target_alias.clone(),
+                    comment: stored.comment.clone(),
+                    ..cred.clone()
+                })
+            }
+            (PlatformCredential::Mac(cred), PlatformCredential::Mac(stored)) => {
+                PlatformCredential::Mac(MacCredential {
+                    creator_code: cred.creator_code.or(stored.creator_code),
+                    type_code: cred.type_code.or(stored.type_code),
+                    comment: cred.comment.clone().or_else(|| stored.comment.clone()),
+                    ..cred.clone()
+                })
+            }
+            _ => self.clone(),
+        }
+    }
+
+    // The same credential, but noting the given expiry in its user-visible
+    // comment: appended to a Windows comment (replacing any expiry already
+    // there), or as the comment of a Mac item.  Linux items have no comment,
+    // so they are unchanged.
+    pub(crate) fn with_expiry(&self, expires: SystemTime) -> PlatformCredential {
+        let note = format!("{}{}", EXPIRY_NOTE, timestamp::format(expires));
+        match self {
+            PlatformCredential::Linux(_) => self.clone(),
+            PlatformCredential::Win(cred) => PlatformCredential::Win(WinCredential {
+                comment: with_note(&cred.comment, note, Some(WIN_COMMENT_MAX)),
+                ..cred.clone()
+            }),
+            PlatformCredential::Mac(cred) => PlatformCredential::Mac(MacCredential {
+                comment: Some(with_note(
+                    cred.comment.as_deref().unwrap_or_default(),
+                    note,
+                    None,
+                )),
+                ..cred.clone()
+            }),
+        }
+    }
+
+    // The expiry noted in this credential's comment (see `with_expiry`),
+    // if it has one.  Linux credentials have no comment, so no expiry.
+    pub fn expiry(&self) -> Option<SystemTime> {
+        match self {
+            PlatformCredential::Linux(_) => None,
+            PlatformCredential::Win(cred) => parse_expiry(&cred.comment),
+            PlatformCredential::Mac(cred) => cred.comment.as_deref().and_then(parse_expiry),
+        }
+    }
+}
+
+const EXPIRY_NOTE: &str = "expires ";
+// The longest comment a Windows credential can have, in bytes (see
+// `CRED_MAX_STRING_LENGTH`).
+const WIN_COMMENT_MAX: usize = 256;
+
+// Recover the expiry noted in a comment by `with_expiry`, if there is one.
+pub(crate) fn parse_expiry(comment: &str) -> Option<SystemTime> {
+    let at = comment.rfind(EXPIRY_NOTE)?;
+    timestamp::parse(&comment[at + EXPIRY_NOTE.len()..])
+}
+
+// A comment with the given expiry note at its end, replacing the note
+// already there (if there is one).  Only a note that parses counts, so
+// other text mentioning "expires " is kept.
+fn with_note(comment: &str, note: String, limit: Option<usize>) -> String {
+    let mut comment = match comment.rfind(EXPIRY_NOTE) {
+        Some(at) if parse_expiry(comment).is_some() => &comment[..at],
+        _ => comment,
+    };
+    // the rest of the comment is cut short, if need be, to make room
+    if let Some(limit) = limit {
+        let mut room = limit
+            .saturating_sub(note.len() + "; ".len())
+            .min(comment.len());
+        while !comment.is_char_boundary(room) {
+            room -= 1;
+        }
+        comment = &comment[..room];
+    }
+    let comment = comment.trim_end().trim_end_matches(';').trim_end();
+    if comment.is_empty() {
+        note
+    } else {
+        format!("{}; {}", comment, note)
+    }
 }
 
+const COMPANION_ATTRIBUTE: &str = "keyring-rs:companion";
+const COMPANION_PREFIX: &str = "keyring-rs:";
+
 // Create the default target credential for a keyring item.  The caller
 // can provide a target parameter to influence the mapping.
 pub fn default_target(
@@ -190,6 +564,64 @@ pub fn default_target(
             domain: target.into(),
             service: service.to_string(),
             account: username.to_string(),
+            creator_code: None,
+            type_code: None,
+            comment: None,
         }),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_note() {
+        let note = || "expires 2025-01-01T00:00:00Z".to_string();
+        let cases = [
+            ("", "expires 2025-01-01T00:00:00Z"),
+            ("mine", "mine; expires 2025-01-01T00:00:00Z"),
+            (
+                "mine; expires 2021-03-04T05:06:07Z",
+                "mine; expires 2025-01-01T00:00:00Z",
+            ),
+            (
+                "token expires when revoked",
+                "token expires when revoked; expires 2025-01-01T00:00:00Z",
+            ),
+        ];
+        for (comment, expected) in cases {
+            assert_eq!(with_note(comment, note(), None), expected, "{}", comment);
+        }
+        // a long comment is cut short to fit, dropping an earlier note
+        let long = "é".repeat(125);
+        let noted = with_note(&long, note(), Some(WIN_COMMENT_MAX));
+        assert!(noted.len() <= WIN_COMMENT_MAX);
+        assert!(noted.ends_with("é; expires 2025-01-01T00:00:00Z"));
+        let noted = with_note(
+            &format!("{}; {}", "x".repeat(200), note()),
+            note(),
+            Some(60),
+        );
+        assert_eq!(noted, format!("{}; {}", "x".repeat(30), note()));
+    }
+
+    #[test]
+    fn test_expiry_fits_windows_comment() {
+        let cred = PlatformCredential::Win(WinCredential {
+            username: "user".to_string(),
+            target_name: "user.svc".to_string(),
+            target_alias: String::new(),
+            comment: "c".repeat(250),
+        });
+        let expires = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        match cred.with_expiry(expires) {
+            PlatformCredential::Win(noted) => {
+                assert!(noted.comment.len() <= WIN_COMMENT_MAX);
+                assert!(noted.comment.starts_with("ccc"));
+            }
+            other => panic!("{:?}", other),
+        }
+        assert_eq!(cred.with_expiry(expires).expiry(), Some(expires));
+    }
+}
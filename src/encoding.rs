@@ -0,0 +1,233 @@
+/*
+Passwords written by other tools aren't always in the encoding this
+crate uses for the platform (UTF-8 on Linux and Mac, UTF-16LE on
+Windows): some Windows tools store UTF-8 in a credential's blob, and
+some older tools store Latin-1.  `Entry::get_password_detect` reads such
+a password by guessing its encoding from the stored bytes.
+
+The stored bytes are tried as UTF-8, then as UTF-16LE, then as Latin-1,
+and the first decoding that is clean is used, where clean means that it
+has no control characters other than tabs and line breaks.  (So the
+UTF-16LE of an ASCII password, which is valid UTF-8 but full of NULs,
+isn't taken for UTF-8.)  A UTF-16LE decoding with private-use
+characters isn't clean either, since that's what Latin-1 with accented
+letters often decodes to as UTF-16LE.  Latin-1 decodes any bytes, but
+not always cleanly, in which case the password fails with
+`BadEncoding`.
+
+This is a guess, not a determination: a password that happens to be
+clean in more than one encoding is decoded in the first of them.
+
+A password this crate stores in a chosen encoding (see
+`Entry::set_password_encoded`) needn't be guessed at: its encoding is
+recorded in a marker, kept in a companion credential.  Entries
+configured to use markers (see `Config::with_encoding_markers`) decode
+by the marker whenever they read the password, including with
+`get_password_detect`, and remove it when they set the password in the
+platform's encoding or delete it.  Other entries ignore markers, since
+reading one costs an extra access of secure storage.
+ */
+
+use crate::credential::PlatformCredential;
+use crate::{platform, Error, Platform, Result};
+
+pub(crate) const PURPOSE: &str = "encoding";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Latin1,
+}
+
+// The bytes a password read with the platform's own encoding was
+// stored as.
+pub(crate) fn stored_bytes(password: &str) -> Vec<u8> {
+    match crate::platform() {
+        Platform::Windows => password
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect(),
+        _ => password.as_bytes().to_vec(),
+    }
+}
+
+impl Encoding {
+    fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Utf16Le => "utf-16le",
+            Encoding::Latin1 => "latin-1",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Encoding> {
+        [Encoding::Utf8, Encoding::Utf16Le, Encoding::Latin1]
+            .iter()
+            .find(|encoding| encoding.name() == name)
+            .copied()
+    }
+}
+
+// The bytes of a password in the given encoding, if it can be encoded in
+// it (Latin-1 has only the first 256 characters).
+pub(crate) fn encode(password: &str, encoding: Encoding) -> Result<Vec<u8>> {
+    match encoding {
+        Encoding::Utf8 => Ok(password.as_bytes().to_vec()),
+        Encoding::Utf16Le => Ok(password
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect()),
+        Encoding::Latin1 => password
+            .chars()
+            .map(|c| (c <= '\u{ff}').then_some(c as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| {
+                Error::BadFormat("password has characters Latin-1 can't encode".to_string())
+            }),
+    }
+}
+
+// Decode the stored bytes with the given encoding, if they're valid in it.
+pub(crate) fn decode(bytes: &[u8], encoding: Encoding) -> Option<String> {
+    match encoding {
+        Encoding::Utf8 => String::from_utf8(bytes.to_vec()).ok(),
+        Encoding::Utf16Le => {
+            if !bytes.len().is_multiple_of(2) {
+                return None;
+            }
+            let units: Vec<u16> = bytes
+                .chunks_exact(2)
+                .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16(&units).ok()
+        }
+        Encoding::Latin1 => Some(bytes.iter().map(|&byte| byte as char).collect()),
+    }
+}
+
+// Read a credential's password, decoding it in the marked encoding if
+// there's a marker, and otherwise the platform's.
+pub(crate) fn read_password(
+    target: &mut PlatformCredential,
+    marker: Option<Encoding>,
+) -> Result<String> {
+    let encoding = match marker {
+        Some(encoding) => encoding,
+        None => return platform::get_password(target),
+    };
+    let secret = platform::get_secret(target)?;
+    match decode(&secret, encoding) {
+        Some(password) => Ok(password),
+        None => Err(Error::BadEncoding(secret)),
+    }
+}
+
+pub(crate) fn read_marker(target: &PlatformCredential) -> Result<Option<Encoding>> {
+    match platform::get_password(&mut target.companion(PURPOSE)) {
+        Ok(stored) => match Encoding::from_name(&stored) {
+            Some(encoding) => Ok(Some(encoding)),
+            None => Err(Error::BadFormat(format!("encoding marker '{}'", stored))),
+        },
+        Err(Error::NoEntry) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+pub(crate) fn write_marker(target: &PlatformCredential, encoding: Encoding) -> Result<()> {
+    platform::set_password(&target.companion(PURPOSE), encoding.name())
+}
+
+// Remove the encoding marker of a credential, if it has one.
+pub(crate) fn delete_marker(target: &PlatformCredential) -> Result<()> {
+    match platform::delete_password(&target.companion(PURPOSE)) {
+        Ok(()) | Err(Error::NoEntry) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+// Decode the stored bytes with the first encoding they are clean in.
+pub(crate) fn detect(bytes: &[u8]) -> Option<(String, Encoding)> {
+    if let Some(decoded) = String::from_utf8(bytes.to_vec())
+        .ok()
+        .filter(|s| is_clean(s))
+    {
+        return Some((decoded, Encoding::Utf8));
+    }
+    if let Some(decoded) = decode_utf16le(bytes).filter(|s| is_clean(s)) {
+        return Some((decoded, Encoding::Utf16Le));
+    }
+    let decoded: String = bytes.iter().map(|&byte| byte as char).collect();
+    is_clean(&decoded).then_some((decoded, Encoding::Latin1))
+}
+
+fn decode_utf16le(bytes: &[u8]) -> Option<String> {
+    decode(bytes, Encoding::Utf16Le).filter(|decoded| !decoded.chars().any(is_private_use))
+}
+
+fn is_private_use(c: char) -> bool {
+    matches!(c, '\u{E000}'..='\u{F8FF}' | '\u{F0000}'..='\u{10FFFF}')
+}
+
+fn is_clean(decoded: &str) -> bool {
+    !decoded
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        let utf16: Vec<u8> = "pässword"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        let cases: [(&[u8], &str, Encoding); 5] = [
+            (b"password", "password", Encoding::Utf8),
+            ("pässword".as_bytes(), "pässword", Encoding::Utf8),
+            (&utf16, "pässword", Encoding::Utf16Le),
+            (b"p\xe4ssword", "pässword", Encoding::Latin1),
+            (b"", "", Encoding::Utf8),
+        ];
+        for (bytes, decoded, encoding) in cases {
+            assert_eq!(
+                detect(bytes),
+                Some((decoded.to_string(), encoding)),
+                "{:?}",
+                bytes
+            );
+        }
+        // control characters in every encoding
+        assert_eq!(detect(b"\x01\x02\x03"), None);
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        for encoding in [Encoding::Utf8, Encoding::Utf16Le, Encoding::Latin1] {
+            let bytes = encode("pässword", encoding).unwrap();
+            assert_eq!(decode(&bytes, encoding).unwrap(), "pässword");
+            assert_eq!(Encoding::from_name(encoding.name()), Some(encoding));
+        }
+        assert_eq!(
+            encode("pässword", Encoding::Latin1).unwrap(),
+            b"p\xe4ssword"
+        );
+        assert!(matches!(
+            encode("桜", Encoding::Latin1),
+            Err(Error::BadFormat(_))
+        ));
+        assert_eq!(decode(b"odd", Encoding::Utf16Le), None);
+    }
+
+    #[test]
+    fn test_stored_bytes_round_trip() {
+        let password = "このきれいな花は桜です";
+        assert_eq!(
+            detect(&stored_bytes(password)).map(|(decoded, _)| decoded),
+            Some(password.to_string())
+        );
+    }
+}
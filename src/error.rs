@@ -10,14 +10,60 @@ pub enum Error {
     // might be that the credential store is locked.  The underlying
     // platform error will typically give the reason.
     NoStorageAccess(crate::platform::Error),
+    // This indicates that the platform asked the user to allow
+    // access to secure storage (for example, to unlock it), and
+    // the user dismissed the request.  That is the user declining,
+    // rather than the platform denying access, so it may make sense
+    // to ask again.  The underlying platform error is attached.
+    UserCanceled(crate::platform::Error),
+    // This indicates that a password couldn't be set or deleted
+    // because the store holding it is read-only (on Mac, a keychain
+    // that is read-only or that the process can't write to).  Reading
+    // from the store may still work.  The underlying platform error is
+    // attached.
+    ReadOnly(crate::platform::Error),
+    // This indicates that the platform needed to ask the user to
+    // allow the operation, but wasn't allowed to: either because the
+    // user was asked too recently (see `Config::with_prompt_interval`),
+    // or because the application has turned off user interaction.
+    // Trying again later may succeed.
+    NoInteraction,
     // This indicates that there is no underlying credential
     // entry in the platform for this item.  Either one was
-    // never set, or it was deleted.
+    // never set, or it was deleted.  Every platform reports
+    // this (and only this) for missing credentials; in particular,
+    // a credential whose password was set to the empty string
+    // exists, and reading it returns the empty string (or fails
+    // with `CorruptCredential`, see `Config::with_empty_as_corrupt`).
     NoEntry,
+    // This indicates that a credential exists but its stored
+    // password is empty, which entries configured to (see
+    // `Config::with_empty_as_corrupt`) take to mean that it
+    // was corrupted, say by a tool that failed part way through
+    // writing it.
+    CorruptCredential,
     // This indicates that the retrieved password blob was not
     // a UTF-8 string.  The underlying bytes are available
     // for examination in the attached value.
     BadEncoding(Vec<u8>),
+    // This indicates that data given to (or stored by) this crate
+    // in one of its own formats, such as a serialized credential,
+    // could not be parsed.  The attached value describes the problem.
+    BadFormat(String),
+    // This indicates a failure reading or writing a file that
+    // this crate manages, such as a cache file.  The underlying
+    // I/O error is attached.
+    Io(std::io::Error),
+    // This indicates that a file this crate manages, such as a cache
+    // file, was written by a later version of the crate in a format
+    // this version doesn't understand.  The file is left as it is.
+    // The attached value is the file's format version.
+    UnsupportedFileVersion(u32),
+    // This wraps an error from an operation on a tagged entry
+    // (see `Entry::with_tag`).  The attached values are the
+    // entry's tag and the error itself; use `Error::inner` to
+    // get at the error whether or not it has been wrapped.
+    WithContext(String, Box<Error>),
     // This indicates that one of the underlying credential
     // metadata values produced by the mapper exceeded a
     // length limit for the underlying platform.  The
@@ -30,10 +76,105 @@ pub enum Error {
     // run when items are created, this can only be a status
     // returned from `new_with_mapper`.
     WrongCredentialPlatform,
+    // This indicates that the operation was not performed
+    // because the confirmation hook declined to confirm it.
+    Cancelled,
+    // This indicates that a handed-off secret (see `Entry::read_handoff`)
+    // couldn't be read with the given token, because it isn't the token
+    // the secret was handed off with.  The secret is left in place.
+    BadHandoffToken,
+    // This indicates that there is no alias (see `set_alias`) with
+    // the attached name.
+    UnknownAlias(String),
+    // This indicates that a step of the self test (see `self_test`)
+    // succeeded, but with the wrong result.  The attached value says
+    // what was wrong.
+    SelfTestFailed(&'static str),
+    // This indicates that a password was not read because its access
+    // schedule (see `Entry::set_password_with_schedule`) doesn't allow
+    // reading it at this time.
+    OutsideSchedule,
+    // This indicates that a password was not stored because the
+    // configured policy (see `Config::with_policy`) rejected it.
+    // The attached value says why.
+    PolicyViolation(PolicyViolation),
+    // This indicates that a scan of the store (see `find_where`) was
+    // not done because the store has more credentials than the scan
+    // may examine.  The attached value is that cap.
+    TooManyCredentials(usize),
+    // This indicates that the Linux collection with the attached name
+    // doesn't exist, and wasn't created (see
+    // `Config::with_collection_policy`).
+    NoSuchCollection(String),
+    // This indicates that the platform's secure storage lacks
+    // capabilities that were required of it (see
+    // `require_capabilities`).  The attached value names them.
+    Unsupported(Vec<&'static str>),
+    // This indicates that a password was not read because it was stored
+    // for a different context (see `Entry::get_password_for_context`).
+    // The attached values are the stored context and the requested one.
+    ContextMismatch(String, String),
+}
+
+// The reason a policy rejected a secret.  This is shown in errors, so
+// it should describe the rule the secret broke, never the secret itself.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyViolation(pub String);
+
+impl std::fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+impl Error {
+    // The error itself, without any `WithContext` wrapper.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::WithContext(_, err) => err.inner(),
+            err => err,
+        }
+    }
+
+    // Whether the operation that failed with this error may succeed if
+    // it's retried, without anything else changing: that is, whether the
+    // error comes from the state of secure storage at the moment (it's
+    // locked, or there's no logon session yet, or a prompt wasn't allowed)
+    // rather than from the request itself.  Missing credentials, bad data,
+    // declined confirmations and the user canceling are all permanent.
+    pub fn is_transient(&self) -> bool {
+        match self.inner() {
+            Error::NoStorageAccess(_) | Error::NoInteraction => true,
+            err => crate::platform::is_transient(err),
+        }
+    }
+
+    // The platform's own description of the platform error behind this
+    // error, if there is one and the platform can describe it (in the
+    // user's language, where the platform supports that).  This is often
+    // more helpful to show the user than the error code.
+    pub fn platform_message(&self) -> Option<String> {
+        match self.inner() {
+            Error::PlatformFailure(err)
+            | Error::NoStorageAccess(err)
+            | Error::UserCanceled(err)
+            | Error::ReadOnly(err) => crate::platform::error_message(err),
+            _ => None,
+        }
+    }
+
+    // The tag of the entry whose operation produced this error, if it
+    // was a tagged entry.
+    pub fn tag(&self) -> Option<&str> {
+        match self {
+            Error::WithContext(tag, _) => Some(tag),
+            _ => None,
+        }
+    }
+}
+
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -41,11 +182,65 @@ impl std::fmt::Display for Error {
                 write!(f, "CredentialMapper value doesn't match this platform")
             }
             Error::PlatformFailure(err) => write!(f, "Platform secure storage failure: {}", err),
+            Error::ReadOnly(err) => write!(f, "Secure storage is read-only: {}", err),
             Error::NoStorageAccess(err) => {
                 write!(f, "Couldn't access platform secure storage: {}", err)
             }
+            Error::UserCanceled(err) => {
+                write!(f, "User canceled access to secure storage: {}", err)
+            }
+            Error::NoInteraction => {
+                write!(
+                    f,
+                    "Secure storage access needs user interaction, which isn't allowed now"
+                )
+            }
             Error::NoEntry => write!(f, "No matching entry found in secure storage"),
+            Error::CorruptCredential => {
+                write!(
+                    f,
+                    "Entry found in secure storage is corrupt (its password is empty)"
+                )
+            }
             Error::BadEncoding(_) => write!(f, "Password cannot be UTF-8 encoded"),
+            Error::BadFormat(reason) => write!(f, "Badly formatted data: {}", reason),
+            Error::Io(err) => write!(f, "File access failure: {}", err),
+            Error::UnsupportedFileVersion(version) => {
+                write!(f, "File format version {} is not supported", version)
+            }
+            Error::WithContext(tag, err) => write!(f, "[{}] {}", tag, err),
+            Error::Cancelled => write!(f, "Operation cancelled by the confirmation hook"),
+            Error::BadHandoffToken => write!(f, "Wrong token for the handed-off secret"),
+            Error::UnknownAlias(alias) => write!(f, "No credential has the alias '{}'", alias),
+            Error::SelfTestFailed(reason) => {
+                write!(f, "Secure storage self test failed: {}", reason)
+            }
+            Error::OutsideSchedule => {
+                write!(f, "Password can't be read outside its access schedule")
+            }
+            Error::PolicyViolation(reason) => {
+                write!(f, "Password rejected by the policy: {}", reason)
+            }
+            Error::NoSuchCollection(name) => {
+                write!(f, "There is no secret service collection '{}'", name)
+            }
+            Error::ContextMismatch(stored, requested) => write!(
+                f,
+                "Password is for context '{}', not '{}'",
+                stored, requested
+            ),
+            Error::Unsupported(missing) => write!(
+                f,
+                "This platform's secure storage doesn't support: {}",
+                missing.join(", ")
+            ),
+            Error::TooManyCredentials(cap) => {
+                write!(
+                    f,
+                    "Secure storage has more than {} credentials to scan",
+                    cap
+                )
+            }
             Error::TooLong(name, len) => write!(
                 f,
                 "Attribute '{}' is longer than platform limit of {} chars",
@@ -60,7 +255,55 @@ impl std::error::Error for Error {
         match self {
             Error::PlatformFailure(err) => Some(err),
             Error::NoStorageAccess(err) => Some(err),
+            Error::UserCanceled(err) => Some(err),
+            Error::ReadOnly(err) => Some(err),
+            Error::Io(err) => Some(err),
+            Error::WithContext(_, err) => Some(err.as_ref()),
             _ => None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The variants wrapping a platform error are classified in the
+    // platform modules' tests.
+    #[test]
+    fn test_transient_classification() {
+        let cases = [
+            (Error::NoInteraction, true),
+            (Error::NoEntry, false),
+            (Error::CorruptCredential, false),
+            (Error::BadEncoding(vec![0xff]), false),
+            (Error::BadFormat("bad".to_string()), false),
+            (Error::Io(std::io::Error::other("io")), false),
+            (Error::UnsupportedFileVersion(2), false),
+            (Error::TooLong("service".to_string(), 128), false),
+            (Error::WrongCredentialPlatform, false),
+            (Error::Cancelled, false),
+            (Error::OutsideSchedule, false),
+            (Error::SelfTestFailed("wrong"), false),
+            (Error::UnknownAlias("alias".to_string()), false),
+            (Error::BadHandoffToken, false),
+            (Error::TooManyCredentials(1000), false),
+            (Error::NoSuchCollection("work".to_string()), false),
+            (Error::Unsupported(vec!["enumeration"]), false),
+            (
+                Error::ContextMismatch("staging".to_string(), "prod".to_string()),
+                false,
+            ),
+            (
+                Error::PolicyViolation(PolicyViolation("too short".to_string())),
+                false,
+            ),
+        ];
+        for (err, transient) in cases {
+            assert_eq!(err.is_transient(), transient, "{:?}", err);
+            let wrapped = Error::WithContext("tag".to_string(), Box::new(err));
+            assert_eq!(wrapped.is_transient(), transient, "{:?}", wrapped);
+            assert!(wrapped.platform_message().is_none());
+        }
+    }
+}
@@ -0,0 +1,217 @@
+/*
+Entries can be put in a named group (see `Entry::with_group`), so that
+related credentials (say, an access token, its refresh token, and some
+configuration) can all be deleted together with `delete_group`.
+
+The members of each group are recorded, serialized, in a registry: the
+group companions of credentials named for the group in the platform's
+default store, as many as it takes to hold the members.  Each member
+also records its group in its group-membership companion.  Setting a
+password through an entry in a group adds its credential to the
+registry, and deleting it (through the entry, or with
+`delete_matching`) removes it.  Credentials not in any group are never
+affected.
+
+Deleting a group isn't atomic (secure storage has no transactions), but
+it is restartable: members that couldn't be deleted stay registered,
+so deleting the group again picks up where the last attempt stopped.
+ */
+
+use crate::credential::{default_target, PlatformCredential};
+use crate::error::{Error, Result};
+use crate::serialized::{escape, unescape};
+use crate::{access_log, platform, Entry};
+
+const PURPOSE: &str = "group";
+const MEMBERSHIP: &str = "group-membership";
+
+// Delete every credential in the given group, returning how many were
+// deleted.  Each deletion must be confirmed by the confirmation hook, if
+// there is one; members it declines stay in the group.  Any access logs
+// of the deleted credentials are deleted as well.
+pub fn delete_group(group: &str) -> Result<usize> {
+    let mut members = read(group)?.into_iter();
+    let mut kept = Vec::new();
+    let mut deleted = 0;
+    while let Some(member) = members.next() {
+        match Entry::new_with_credential(&member)?.delete_password() {
+            Ok(()) => {
+                // the member is gone, so failing to clean up after it
+                // doesn't stop the rest of the group being deleted
+                let _ = access_log::delete(&member);
+                let _ = forget_membership(&member);
+                deleted += 1;
+            }
+            // deleted by someone else since it was registered
+            Err(Error::NoEntry) => continue,
+            Err(Error::Cancelled) => kept.push(member),
+            Err(err) => {
+                kept.push(member);
+                kept.extend(members);
+                write(group, &kept)?;
+                return Err(err);
+            }
+        }
+    }
+    write(group, &kept)?;
+    Ok(deleted)
+}
+
+// Record that the credential is in the group.
+pub(crate) fn join(group: &str, member: &PlatformCredential) -> Result<()> {
+    let mut members = read(group)?;
+    if !members.contains(member) {
+        members.push(member.clone());
+        write(group, &members)?;
+        platform::set_password(&member.companion(MEMBERSHIP), group)?;
+    }
+    Ok(())
+}
+
+// Record that the credential is no longer in the group.
+pub(crate) fn leave(group: &str, member: &PlatformCredential) -> Result<()> {
+    let mut members = read(group)?;
+    let count = members.len();
+    members.retain(|registered| registered != member);
+    if members.len() != count {
+        write(group, &members)?;
+    }
+    forget_membership(member)
+}
+
+// Put the credential that replaces a member (see `rekey_username`) in the
+// member's group, in place of the member.  A credential that was in a
+// different group leaves it.
+pub(crate) fn rekey(member: &PlatformCredential, replacement: &PlatformCredential) -> Result<()> {
+    match platform::get_password(&mut member.companion(MEMBERSHIP)) {
+        Ok(group) => {
+            leave_any(replacement)?;
+            join(&group, replacement)?;
+            leave(&group, member)
+        }
+        Err(Error::NoEntry) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+// Take the credential out of whichever group it's in, for callers (such
+// as `delete_matching`) that don't know its group.
+pub(crate) fn leave_any(member: &PlatformCredential) -> Result<()> {
+    match platform::get_password(&mut member.companion(MEMBERSHIP)) {
+        Ok(group) => leave(&group, member),
+        Err(Error::NoEntry) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+fn forget_membership(member: &PlatformCredential) -> Result<()> {
+    match platform::delete_password(&member.companion(MEMBERSHIP)) {
+        Ok(()) | Err(Error::NoEntry) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+// The registry of a group is split into pages, so that it fits in secure
+// storage however many members the group has.  The first page has the
+// group's name as its username; later pages have the page number, too.
+fn registry(group: &str, page: usize) -> PlatformCredential {
+    let username = match page {
+        0 => "keyring-rs".to_string(),
+        page => format!("keyring-rs:{}", page),
+    };
+    default_target(&crate::platform(), None, group, &username).companion(PURPOSE)
+}
+
+// Members that can't be parsed (or are for another platform) are skipped.
+fn read(group: &str) -> Result<Vec<PlatformCredential>> {
+    let mut members = Vec::new();
+    for page in 0.. {
+        match platform::get_password(&mut registry(group, page)) {
+            Ok(registered) => members.extend(parse_members(&registered)),
+            Err(Error::NoEntry) => break,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(members)
+}
+
+// An empty group has no registry.  Pages no longer needed are deleted.
+fn write(group: &str, members: &[PlatformCredential]) -> Result<()> {
+    let pages = paginate(&format_members(members), platform::password_capacity());
+    for (page, registered) in pages.iter().enumerate() {
+        platform::set_password(&registry(group, page), registered)?;
+    }
+    for page in pages.len().. {
+        match platform::delete_password(&registry(group, page)) {
+            Ok(()) => continue,
+            Err(Error::NoEntry) => break,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(())
+}
+
+// Split the lines of a registry into pages that each fit in the given
+// number of bytes (if there is a limit).  A line too long for any page
+// gets a page of its own.
+fn paginate(registry: &str, limit: Option<usize>) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+    for line in registry.split_inclusive('\n') {
+        let full = limit.is_some_and(|limit| {
+            platform::password_len(&page) + platform::password_len(line) > limit
+        });
+        if full && !page.is_empty() {
+            pages.push(std::mem::take(&mut page));
+        }
+        page.push_str(line);
+    }
+    if !page.is_empty() {
+        pages.push(page);
+    }
+    pages
+}
+
+// Each member is stored on its own line, as its escaped serialized form.
+fn format_members(members: &[PlatformCredential]) -> String {
+    let mut registry = String::new();
+    for member in members {
+        registry.push_str(&escape(&member.to_serialized()));
+        registry.push('\n');
+    }
+    registry
+}
+
+fn parse_members(registry: &str) -> Vec<PlatformCredential> {
+    registry
+        .lines()
+        .filter_map(|line| PlatformCredential::from_serialized(&unescape(line)?).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_members_round_trip() {
+        let platform = crate::platform();
+        let members = vec![
+            default_target(&platform, None, "group service", "first"),
+            default_target(&platform, Some("target"), "group service", "second\nline"),
+        ];
+        assert_eq!(parse_members(&format_members(&members)), members);
+        assert!(parse_members("garbage\n\n").is_empty());
+    }
+
+    #[test]
+    fn test_paginate() {
+        let line_len = platform::password_len("member\n");
+        let registry = "member\n".repeat(5);
+        let pages = paginate(&registry, Some(2 * line_len));
+        assert_eq!(pages, ["member\nmember\n", "member\nmember\n", "member\n"]);
+        assert_eq!(paginate(&registry, None), [registry.as_str()]);
+        assert_eq!(paginate(&registry, Some(1)).len(), 5);
+        assert!(paginate("", Some(1)).is_empty());
+    }
+}
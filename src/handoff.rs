@@ -0,0 +1,91 @@
+/*
+A handoff passes a secret from one process to another (say, from a
+privileged helper to the unprivileged child it starts) through secure
+storage, without leaving it there.  The giving process sets the secret
+(see `Entry::set_handoff`) and gets a token, which it passes to the
+receiving process; the receiving process reads the secret with the
+token (see `Entry::read_handoff`), which deletes it.
+
+The token is the only way to the secret: the secret is encrypted (with
+ChaCha20-Poly1305) under the token, which itself is never stored, so
+even a process that can read the stored credential can't recover the
+secret without the token.  A wrong token fails with `BadHandoffToken`
+and leaves the secret in place for the right one.  The secret is read
+at most once: it is deleted before it is returned, and if it can't be
+deleted, it isn't returned.
+
+The secret is kept in a companion credential of the entry's own
+credential, so an entry has at most one handoff at a time.  A secret
+that is never read stays in secure storage (encrypted) until another
+handoff replaces it.
+
+This module is only available with the `handoff` feature.
+ */
+
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+
+use crate::credential::PlatformCredential;
+use crate::serialized::{hex, unhex};
+use crate::{platform, zeroing, Error, Result};
+
+pub(crate) const PURPOSE: &str = "handoff";
+const NONCE_LEN: usize = 12;
+
+// The capability to read a handed-off secret.  Its string form is
+// what should be passed to the receiving process, which should treat
+// it as carefully as the secret itself.
+#[derive(Clone, PartialEq, Eq)]
+pub struct HandoffToken(pub String);
+
+// Tokens are never shown, so they can't leak through logging.
+impl std::fmt::Debug for HandoffToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("HandoffToken(..)")
+    }
+}
+
+// Store the secret for the credential, encrypted under a new random
+// token, replacing any handoff that hasn't been read yet.
+pub(crate) fn set(target: &PlatformCredential, secret: &str) -> Result<HandoffToken> {
+    let mut key = [0u8; 32];
+    let mut nonce = [0u8; NONCE_LEN];
+    random(&mut key)?;
+    random(&mut nonce)?;
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    let encrypted = cipher
+        .encrypt(Nonce::from_slice(&nonce), secret.as_bytes())
+        .map_err(|_| Error::BadFormat("handoff encryption".to_string()))?;
+    let stored = format!("{}{}", hex(&nonce), hex(&encrypted));
+    platform::set_password(&target.companion(PURPOSE), &stored)?;
+    let token = HandoffToken(hex(&key));
+    zeroing::wipe(&mut key[..]);
+    Ok(token)
+}
+
+// Read the secret handed off for the credential, deleting it.
+pub(crate) fn read(target: &PlatformCredential, token: &HandoffToken) -> Result<String> {
+    let companion = target.companion(PURPOSE);
+    let stored = platform::get_password(&mut companion.clone())?;
+    let mut key = match unhex(&token.0) {
+        Some(key) if key.len() == 32 => key,
+        _ => return Err(Error::BadHandoffToken),
+    };
+    let stored = unhex(&stored)
+        .filter(|stored| stored.len() > NONCE_LEN)
+        .ok_or_else(|| Error::BadFormat("stored handoff".to_string()))?;
+    let (nonce, encrypted) = stored.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    zeroing::wipe(&mut key[..]);
+    let decrypted = cipher
+        .decrypt(Nonce::from_slice(nonce), encrypted)
+        .map_err(|_| Error::BadHandoffToken)?;
+    let secret =
+        String::from_utf8(decrypted).map_err(|err| Error::BadEncoding(err.into_bytes()))?;
+    platform::delete_password(&companion)?;
+    Ok(secret)
+}
+
+fn random(bytes: &mut [u8]) -> Result<()> {
+    getrandom::getrandom(bytes).map_err(|err| Error::Io(std::io::Error::other(err)))
+}
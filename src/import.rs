@@ -0,0 +1,288 @@
+/*
+Bulk import of credentials from CSV, for provisioning many credentials
+from a spreadsheet.  Each row of the input names a service and a
+username and, optionally, the password to store for them:
+
+    service,username,password
+
+The input is read one row at a time, so it can be arbitrarily large.
+Fields can be quoted (with `"`), in which case they can contain commas,
+line breaks, and (doubled) quotes.  An initial header row naming the
+columns is skipped.
+
+A row that can't be imported doesn't stop the import: the outcome of
+every row is reported, so the caller can fix and re-import the rows
+that failed.  Only a failure to read the input stops the import.
+
+A verified import (see `import_csv_verified`) also reads back each
+password it stores, to check that it was stored as given.  It can also
+be all or nothing: if any row isn't stored and verified, the passwords
+it stored are rolled back (as in a transaction, so as well as possible)
+once every row has been tried.
+ */
+
+use std::io::{BufRead, BufReader, Read};
+
+use crate::transaction::Transaction;
+use crate::{Entry, Error, Result};
+
+#[derive(Debug)]
+pub struct ImportReport {
+    pub rows: Vec<ImportRow>,
+}
+
+impl ImportReport {
+    // The number of rows whose password was stored.
+    pub fn stored(&self) -> usize {
+        self.rows
+            .iter()
+            .filter(|row| matches!(row.outcome, ImportOutcome::Stored))
+            .count()
+    }
+}
+
+#[derive(Debug)]
+pub struct ImportRow {
+    // The line of the input on which the row starts (counting from 1).
+    pub line: usize,
+    // The row's service and username, if it has them.
+    pub service: String,
+    pub username: String,
+    pub outcome: ImportOutcome,
+}
+
+#[derive(Debug)]
+pub enum ImportOutcome {
+    // The row's password was stored.
+    Stored,
+    // The input has no password column, so there was nothing to store.
+    Skipped,
+    // The row doesn't have the expected columns; the attached value says why.
+    Malformed(String),
+    // Storing the row's password failed with the attached error.
+    Failed(Error),
+    // The row's password was stored, but read back differently (in a
+    // verified import).
+    Mismatch,
+    // The row's password was stored and verified, but rolled back because
+    // another row failed (in a verified import).
+    RolledBack,
+}
+
+// Import the credentials in the given CSV input, whose rows have a service
+// and username column, followed by a password column if `has_password_column`
+// is true.  Rows without a password column are reported as skipped.  This
+// fails with `BadFormat` only if the input can't be read (for example,
+// because it isn't UTF-8).
+pub fn import_csv(reader: impl Read, has_password_column: bool) -> Result<ImportReport> {
+    import_rows(
+        reader,
+        has_password_column,
+        |service, username, password| match Entry::new(service, username).set_password(password) {
+            Ok(()) => ImportOutcome::Stored,
+            Err(err) => ImportOutcome::Failed(err),
+        },
+    )
+}
+
+// Import the credentials in the given CSV input, whose rows have a
+// service, username and password column, reading back each password
+// stored to verify it (rows whose password reads back differently are
+// reported as mismatched).  If `roll_back` is true and any row isn't
+// stored and verified (including malformed rows), the rows that were are
+// rolled back, and reported as such; a row whose rollback fails is still
+// reported as stored.  Like `import_csv`, this fails only if the input
+// can't be read, in which case the rows stored so far are rolled back
+// too if `roll_back` is true.
+pub fn import_csv_verified(reader: impl Read, roll_back: bool) -> Result<ImportReport> {
+    let mut transaction = Transaction::new();
+    let result = import_rows(reader, true, |service, username, password| {
+        let entry = Entry::new(service, username);
+        if let Err(err) = transaction.set(&entry, password) {
+            return ImportOutcome::Failed(err);
+        }
+        match entry.verify_password(password) {
+            Ok(true) => ImportOutcome::Stored,
+            Ok(false) => ImportOutcome::Mismatch,
+            Err(err) => ImportOutcome::Failed(err),
+        }
+    });
+    let mut report = match result {
+        Ok(report) => report,
+        Err(err) => {
+            if !roll_back {
+                transaction.keep();
+            }
+            return Err(err);
+        }
+    };
+    let failed = report.stored() < report.rows.len();
+    if roll_back && failed {
+        let not_undone = transaction.roll_back();
+        for row in report.rows.iter_mut() {
+            let target = Entry::new(&row.service, &row.username).target;
+            if matches!(row.outcome, ImportOutcome::Stored)
+                && !not_undone.iter().any(|entry| entry.target == target)
+            {
+                row.outcome = ImportOutcome::RolledBack;
+            }
+        }
+    }
+    transaction.keep();
+    Ok(report)
+}
+
+// Import the rows of the given CSV input, storing the password of each
+// well-formed row (if the input has a password column) with `store`.
+fn import_rows(
+    reader: impl Read,
+    has_password_column: bool,
+    mut store: impl FnMut(&str, &str, &str) -> ImportOutcome,
+) -> Result<ImportReport> {
+    let columns = if has_password_column { 3 } else { 2 };
+    let mut lines = BufReader::new(reader).lines();
+    let mut line = 0;
+    let mut rows = Vec::new();
+    while let Some((start, fields)) = read_record(&mut lines, &mut line)? {
+        if start == 1 && is_header(&fields) {
+            continue;
+        }
+        let service = fields.first().cloned().unwrap_or_default();
+        let username = fields.get(1).cloned().unwrap_or_default();
+        let outcome = if fields.len() != columns {
+            ImportOutcome::Malformed(format!(
+                "expected {} columns, found {}",
+                columns,
+                fields.len()
+            ))
+        } else if service.is_empty() || username.is_empty() {
+            ImportOutcome::Malformed("empty service or username".to_string())
+        } else if !has_password_column {
+            ImportOutcome::Skipped
+        } else {
+            store(&service, &username, &fields[2])
+        };
+        rows.push(ImportRow {
+            line: start,
+            service,
+            username,
+            outcome,
+        });
+    }
+    Ok(ImportReport { rows })
+}
+
+fn is_header(fields: &[String]) -> bool {
+    let names = ["service", "username", "password"];
+    fields.len() <= names.len()
+        && fields
+            .iter()
+            .zip(names.iter())
+            .all(|(field, name)| field.trim().eq_ignore_ascii_case(name))
+}
+
+// Read the next record, which may span several lines if a quoted field
+// contains line breaks.  Returns the line the record starts on, and its
+// fields.  Blank lines are skipped.
+fn read_record(
+    lines: &mut impl Iterator<Item = std::io::Result<String>>,
+    line: &mut usize,
+) -> Result<Option<(usize, Vec<String>)>> {
+    let read_error = |err: std::io::Error| Error::BadFormat(format!("CSV input: {}", err));
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut start = None;
+    for text in lines {
+        let text = text.map_err(read_error)?;
+        *line += 1;
+        if start.is_none() {
+            if text.trim().is_empty() {
+                continue;
+            }
+            start = Some(*line);
+        } else {
+            // a line break inside a quoted field
+            field.push('\n');
+        }
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if quoted && chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = !quoted,
+                ',' if !quoted => fields.push(std::mem::take(&mut field)),
+                c => field.push(c),
+            }
+        }
+        if !quoted {
+            break;
+        }
+    }
+    Ok(start.map(|start| {
+        fields.push(field);
+        (start, fields)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(input: &str) -> Vec<(usize, Vec<String>)> {
+        let mut lines = BufReader::new(input.as_bytes()).lines();
+        let mut line = 0;
+        let mut records = Vec::new();
+        while let Some(record) = read_record(&mut lines, &mut line).unwrap() {
+            records.push(record);
+        }
+        records
+    }
+
+    #[test]
+    fn test_read_records() {
+        let input = "a,b,c\n\n\"with, comma\",\"two\nlines\",\"say \"\"hi\"\"\"\nlast,";
+        assert_eq!(
+            records(input),
+            vec![
+                (1, vec!["a".into(), "b".into(), "c".into()]),
+                (
+                    3,
+                    vec![
+                        "with, comma".into(),
+                        "two\nlines".into(),
+                        "say \"hi\"".into()
+                    ]
+                ),
+                (5, vec!["last".into(), "".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_malformed_rows_reported() {
+        // none of these rows reach secure storage
+        let input = "Service,Username\nonly one\n,empty\ntoo,many,columns\nsvc,user\n";
+        let report = import_csv(input.as_bytes(), false).unwrap();
+        let outcomes: Vec<&ImportOutcome> = report.rows.iter().map(|row| &row.outcome).collect();
+        assert!(matches!(
+            outcomes[..],
+            [
+                ImportOutcome::Malformed(_),
+                ImportOutcome::Malformed(_),
+                ImportOutcome::Malformed(_),
+                ImportOutcome::Skipped
+            ]
+        ));
+        assert_eq!(report.rows[3].line, 5);
+        assert_eq!(report.stored(), 0);
+    }
+
+    #[test]
+    fn test_unreadable_input() {
+        let input: &[u8] = b"svc,user,\xff\n";
+        assert!(matches!(import_csv(input, true), Err(Error::BadFormat(_))));
+    }
+}
@@ -0,0 +1,160 @@
+/*
+An inventory is a report of the credentials an application manages,
+with their metadata, suitable for loading into a spreadsheet or other
+reporting tool.  It never contains passwords.
+
+The report has one row per credential, with these columns:
+
+- service and username: as reported by `PlatformCredential`
+- store: the Linux collection, the Mac keychain domain, or (on Windows,
+  where there is only one store) `credential manager`
+- created and last_modified: ISO-8601 UTC times, empty (or null) where
+  the platform doesn't record them
+- persistence: the Windows persistence of the credential, empty (or null)
+  on other platforms
+ */
+
+use crate::credential::{CredentialMetadata, PlatformCredential};
+use crate::{timestamp, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InventoryFormat {
+    // Comma-separated values, with a header row.
+    Csv,
+    // An array of objects, one per credential, keyed by column name.
+    Json,
+}
+
+const COLUMNS: [&str; 6] = [
+    "service",
+    "username",
+    "store",
+    "created",
+    "last_modified",
+    "persistence",
+];
+
+// Produce an inventory of the credentials (in the platform's default
+// store) whose service starts with the given prefix.
+pub fn inventory(prefix: &str, format: InventoryFormat) -> Result<String> {
+    let rows: Vec<Row> = crate::enumerate_with_metadata(prefix)?
+        .iter()
+        .map(|(credential, metadata)| row(credential, metadata))
+        .collect();
+    Ok(match format {
+        InventoryFormat::Csv => to_csv(&rows),
+        InventoryFormat::Json => to_json(&rows),
+    })
+}
+
+type Row = [Option<String>; 6];
+
+fn row(credential: &PlatformCredential, metadata: &CredentialMetadata) -> Row {
+    let store = match credential {
+        PlatformCredential::Linux(cred) => cred.collection.clone(),
+        PlatformCredential::Win(_) => "credential manager".to_string(),
+        PlatformCredential::Mac(cred) => format!("{:?}", cred.domain).to_lowercase(),
+    };
+    [
+        Some(credential.service().to_string()),
+        Some(credential.username().to_string()),
+        Some(store),
+        metadata.created.map(timestamp::format),
+        metadata.modified.map(timestamp::format),
+        metadata
+            .persistence
+            .map(|persistence| format!("{:?}", persistence).to_lowercase()),
+    ]
+}
+
+fn to_csv(rows: &[Row]) -> String {
+    let mut csv = COLUMNS.join(",");
+    csv.push('\n');
+    for row in rows {
+        let fields: Vec<String> = row
+            .iter()
+            .map(|field| csv_field(field.as_deref().unwrap_or_default()))
+            .collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    csv
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn to_json(rows: &[Row]) -> String {
+    let objects: Vec<String> = rows
+        .iter()
+        .map(|row| {
+            let fields: Vec<String> = COLUMNS
+                .iter()
+                .zip(row.iter())
+                .map(|(column, field)| match field {
+                    Some(value) => format!("\"{}\":{}", column, json_string(value)),
+                    None => format!("\"{}\":null", column),
+                })
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+    format!("[{}]", objects.join(","))
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_rows() -> Vec<Row> {
+        vec![[
+            Some("my, \"app\"".to_string()),
+            Some("user".to_string()),
+            Some("default".to_string()),
+            Some("2021-01-01T00:00:00Z".to_string()),
+            None,
+            None,
+        ]]
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        assert_eq!(
+            to_csv(&sample_rows()),
+            "service,username,store,created,last_modified,persistence\n\
+             \"my, \"\"app\"\"\",user,default,2021-01-01T00:00:00Z,,\n"
+        );
+    }
+
+    #[test]
+    fn test_json_escaping() {
+        assert_eq!(
+            to_json(&sample_rows()),
+            "[{\"service\":\"my, \\\"app\\\"\",\"username\":\"user\",\"store\":\"default\",\
+             \"created\":\"2021-01-01T00:00:00Z\",\"last_modified\":null,\"persistence\":null}]"
+        );
+        assert_eq!(to_json(&[]), "[]");
+    }
+}
@@ -2,34 +2,358 @@
 //!
 //! Allows for setting and getting passwords on Linux, OSX, and Windows
 
+mod absence;
+pub mod access_log;
+mod alias;
+#[cfg(feature = "cache-file")]
+pub mod cache_file;
+mod capabilities;
+mod classify;
+mod config;
+mod context;
 pub mod credential;
+mod encoding;
 pub mod error;
+mod group;
+#[cfg(feature = "handoff")]
+mod handoff;
+mod import;
+mod inventory;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+mod native;
+mod prompt;
+mod schedule;
+mod self_test;
+mod serialized;
+mod snapshot;
+mod test_credential;
+mod timestamp;
+mod totp;
+mod transaction;
+mod zeroing;
 
-use credential::{Platform, PlatformCredential};
-pub use error::{Error, Result};
+use access_log::{AccessLog, AccessRecord};
+pub use alias::{load_aliases, remove_alias, serialize_aliases, set_alias};
+pub use capabilities::{capabilities, require_capabilities, Capabilities};
+pub use classify::{classify_secret, TokenKind};
+pub use config::{
+    clear_confirm_hook, clear_policy, clear_provisioner, clear_transform, global_config,
+    set_confirm_hook, set_global_config, set_policy, set_provisioner, set_transform,
+    CollectionPolicy, Config, Normalization, SecretTransform,
+};
+use credential::{AccessControl, CredentialMetadata, Platform, PlatformCredential, WinPersistence};
+pub use encoding::Encoding;
+pub use error::{Error, PolicyViolation, Result};
+pub use group::delete_group;
+#[cfg(feature = "handoff")]
+pub use handoff::HandoffToken;
+pub use import::{import_csv, import_csv_verified, ImportOutcome, ImportReport, ImportRow};
+pub use inventory::{inventory, InventoryFormat};
+pub use native::NativeExport;
+pub use schedule::{AccessSchedule, Weekday};
+pub use self_test::self_test;
+pub use snapshot::{snapshot, snapshot_with_secrets, Snapshot, SnapshotItem};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Instant, SystemTime};
+pub use test_credential::TestCredential;
+pub use totp::TotpEntry;
+pub use transaction::{batch, transaction, Batch, BatchReport, Transaction};
 
 // compile-time Platform known at runtime
 pub fn platform() -> Platform {
     platform::platform()
 }
 
+// Find the credentials whose service or username contains the query,
+// ignoring case.  (An empty query matches every credential.)  Only the
+// platform's default store is searched: the default collection on Linux,
+// the User keychain on Mac, and the credential store on Windows.  See
+// `PlatformCredential::service` for how the service of a credential is
+// determined.  This returns credentials, not passwords; use
+// `Entry::new_with_credential` to read the password of any of them.
+pub fn search(query: &str) -> Result<Vec<PlatformCredential>> {
+    let query = query.to_lowercase();
+    let matches = |value: &str| value.to_lowercase().contains(&query);
+    Ok(platform::list_credentials()?
+        .into_iter()
+        .filter(|credential| matches(credential.service()) || matches(credential.username()))
+        .collect())
+}
+
+// The credentials (in the platform's default store, see `search`) whose
+// service starts with the given prefix, each with the metadata the
+// platform keeps about it (see `Entry::get_metadata`).  The metadata is
+// read as the credentials are listed, rather than by looking up each
+// credential again.  This never reads passwords.
+pub fn enumerate_with_metadata(
+    prefix: &str,
+) -> Result<Vec<(PlatformCredential, CredentialMetadata)>> {
+    Ok(platform::list_credentials_with_metadata()?
+        .into_iter()
+        .filter(|(credential, _)| credential.service().starts_with(prefix))
+        .collect())
+}
+
+// The credentials (in the platform's default store, see `search`) last
+// modified at or after `after` and before `before`, as recorded in their
+// metadata: the last write on Windows, and the modification time of a
+// Linux or Mac item (or, if it hasn't one, its creation time).
+// Credentials whose platform records neither are left out.  This never
+// reads passwords.
+pub fn find_modified_between(
+    after: SystemTime,
+    before: SystemTime,
+) -> Result<Vec<PlatformCredential>> {
+    Ok(enumerate_with_metadata("")?
+        .into_iter()
+        .filter(|(_, metadata)| {
+            metadata
+                .modified
+                .or(metadata.created)
+                .is_some_and(|modified| after <= modified && modified < before)
+        })
+        .map(|(credential, _)| credential)
+        .collect())
+}
+
+// How many credentials `find_where` examines, at most, by default.
+pub const DEFAULT_SCAN_CAP: usize = 1000;
+
+// The credentials (in the platform's default store, see `search`) that
+// match the given predicate, which sees each credential's locator (never
+// its password).  Every credential in the store is listed and examined,
+// which is slow on a large store, so this fails with
+// `TooManyCredentials` rather than examine more than `DEFAULT_SCAN_CAP`
+// of them; use `find_where_with_cap` to examine more.
+pub fn find_where(
+    predicate: impl Fn(&PlatformCredential) -> bool,
+) -> Result<Vec<PlatformCredential>> {
+    find_where_with_cap(DEFAULT_SCAN_CAP, predicate)
+}
+
+// Like `find_where`, but failing only if the store has more than `cap`
+// credentials.  (A cap of `usize::MAX` examines the whole store.)
+pub fn find_where_with_cap(
+    cap: usize,
+    predicate: impl Fn(&PlatformCredential) -> bool,
+) -> Result<Vec<PlatformCredential>> {
+    let listed = platform::list_credentials()?;
+    if listed.len() > cap {
+        return Err(Error::TooManyCredentials(cap));
+    }
+    Ok(listed
+        .into_iter()
+        .filter(|credential| predicate(credential))
+        .collect())
+}
+
+// The credentials (in the platform's default store, see `search`) whose
+// service starts with the given prefix and whose password is one of the
+// given weak passwords (such as "" or "changeme"), for an audit to flag.
+// Each password is read (which may prompt the user), compared in
+// constant time against every weak password, and wiped; only the
+// credentials are returned, never their passwords.  A credential
+// deleted before its password is read is left out; if any other
+// password can't be read, the audit fails with that error.
+pub fn audit_weak(weak_list: &[&str], prefix: &str) -> Result<Vec<PlatformCredential>> {
+    let mut weak = Vec::new();
+    for credential in platform::list_credentials()? {
+        if !credential.service().starts_with(prefix) {
+            continue;
+        }
+        let mut password = match platform::get_password(&mut credential.clone()) {
+            Ok(password) => password,
+            Err(Error::NoEntry) => continue,
+            Err(err) => return Err(err),
+        };
+        // every weak password is compared, so the time taken doesn't
+        // reveal which one matched
+        let matched = weak_list.iter().fold(false, |matched, candidate| {
+            constant_time_eq(password.as_bytes(), candidate.as_bytes()) | matched
+        });
+        zeroing::wipe(&mut password);
+        if matched {
+            weak.push(credential);
+        }
+    }
+    Ok(weak)
+}
+
+// Move the password stored for a service and username (as by
+// `Entry::new`) to a different username for the same service, along with
+// the attributes of its credential (such as a Linux item's label, or a Mac
+// item's comment), replacing any password the new username had.  The
+// secret is moved exactly as it's stored, without being normalized,
+// checked or transformed again.  Its companions (its access log,
+// schedule, context, encoding marker, TOTP label and handoff) move with
+// it, replacing those of the new username, and if it's in a group, the
+// new credential takes its place there.  The old credential is deleted
+// once the new one is stored, so a failure never loses the password.
+// Returns whether there was a password to move: if the old username has
+// none, nothing changes.  (The platform's creation and modification
+// times are those of the move.)
+pub fn rekey_username(service: &str, old_username: &str, new_username: &str) -> Result<bool> {
+    let old = Entry::new(service, old_username);
+    let (mut secret, stored) = match old.on_credential(|target| {
+        let mut found = target.clone();
+        platform::get_secret(&mut found).map(|secret| (secret, found))
+    }) {
+        Ok(found) => found,
+        Err(Error::NoEntry) => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    let moved = stored.with_username(new_username);
+    let restored = Entry::new_with_credential(&moved)?.restore_secret(&secret);
+    zeroing::wipe(&mut secret);
+    restored?;
+    // a change of case alone is the same credential on Windows
+    if stored.is_found_as(&moved) {
+        return Ok(true);
+    }
+    for purpose in companion_purposes() {
+        copy_companion(&stored, &moved, purpose)?;
+    }
+    group::rekey(&stored, &moved)?;
+    Entry::new_with_credential(&stored)?.delete_password()?;
+    // the old credential is gone, so failing to clean up after it isn't
+    // a failure to move it
+    for purpose in companion_purposes() {
+        let _ = platform::delete_password(&stored.companion(purpose));
+    }
+    Ok(true)
+}
+
+// The purposes of the companions that describe a credential's password,
+// and so move with it (see `rekey_username`).  Group membership is moved
+// separately, since the group's registry must move with it.
+#[cfg_attr(not(feature = "handoff"), allow(unused_mut))]
+fn companion_purposes() -> Vec<&'static str> {
+    let mut purposes = vec![
+        access_log::PURPOSE,
+        schedule::PURPOSE,
+        context::PURPOSE,
+        encoding::PURPOSE,
+        totp::PURPOSE,
+    ];
+    #[cfg(feature = "handoff")]
+    purposes.push(handoff::PURPOSE);
+    purposes
+}
+
+// Copy a companion of one credential to another, exactly as it's
+// stored, or remove the other's if the one has none.
+fn copy_companion(from: &PlatformCredential, to: &PlatformCredential, purpose: &str) -> Result<()> {
+    match platform::get_secret(&mut from.companion(purpose)) {
+        Ok(mut secret) => {
+            let copied = platform::set_secret(&to.companion(purpose), &secret);
+            zeroing::wipe(&mut secret);
+            copied
+        }
+        Err(Error::NoEntry) => match platform::delete_password(&to.companion(purpose)) {
+            Ok(()) | Err(Error::NoEntry) => Ok(()),
+            Err(err) => Err(err),
+        },
+        Err(err) => Err(err),
+    }
+}
+
+// Whether each of the given entries has a password, in the same order
+// as the entries.  The credentials in the platform's default store (see
+// `search`) are listed just once, and the presence of each entry in that
+// store is found from the list; entries in other stores are checked one
+// at a time (see `Entry::exists`).
+pub fn exists_many(entries: &[Entry]) -> Result<Vec<bool>> {
+    let listed = if entries.iter().any(|entry| entry.target.in_default_store()) {
+        platform::list_credentials()?
+    } else {
+        Vec::new()
+    };
+    entries
+        .iter()
+        .map(|entry| {
+            if entry.target.in_default_store() {
+                Ok(listed
+                    .iter()
+                    .any(|credential| entry.target.is_found_as(credential)))
+            } else {
+                entry.exists()
+            }
+        })
+        .collect()
+}
+
+// The outcome of `Entry::delete_matching_with_progress`.
+#[derive(Debug, Default)]
+pub struct DeletionReport {
+    // The credentials that were deleted, in the order they were deleted.
+    pub deleted: Vec<PlatformCredential>,
+    // The credentials that couldn't be deleted, with the reason why.
+    pub failed: Vec<(PlatformCredential, Error)>,
+}
+
+// Compare secrets in time that depends only on their lengths, so the
+// comparison doesn't reveal how much of a guess was right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 // Platform-specific implementations
 #[cfg_attr(target_os = "linux", path = "linux.rs")]
 #[cfg_attr(target_os = "windows", path = "windows.rs")]
 #[cfg_attr(target_os = "macos", path = "macos.rs")]
 mod platform;
 
-#[derive(Debug)]
+#[cfg(target_os = "macos")]
+pub use platform::{
+    consolidate, find_duplicates_across_keychains, open_keychain_file, KeychainId,
+    ScopedCrateKeychain,
+};
+#[cfg(target_os = "linux")]
+pub use platform::{session_identity, SessionIdentity};
+
+#[derive(Debug, Clone)]
 pub struct Entry {
     target: PlatformCredential,
+    access_log: Option<AccessLog>,
+    config: Config,
+    tag: Option<String>,
+    fallbacks: Vec<Entry>,
+    migrate_fallbacks: bool,
+    group: Option<String>,
 }
 
 impl Entry {
     // Create an entry for the given service and username.
     // This maps to a target credential in the default keychain.
+    // If a store has been selected in the global configuration, the
+    // credential is put there instead.
     pub fn new(service: &str, username: &str) -> Entry {
+        Entry::new_with_config(service, username, Config::new())
+    }
+
+    // Create an entry for the given service and username that uses the
+    // given configuration.  Settings made in this configuration override
+    // the global configuration for this entry.  If the configuration has
+    // a name hasher, the entry's credential is for the hashed names.
+    pub fn new_with_config(service: &str, username: &str, config: Config) -> Entry {
+        // the store selects a collection or keychain, but on Windows a
+        // target is a target name, so the store has to be ignored there
+        let store = match platform() {
+            Platform::Windows => None,
+            _ => config.store(),
+        };
+        let service = config.hash_name(service);
+        let username = config.hash_name(username);
         Entry {
-            target: credential::default_target(&platform(), None, service, username),
+            target: credential::default_target(&platform(), store.as_deref(), &service, &username),
+            access_log: None,
+            config,
+            tag: None,
+            fallbacks: Vec::new(),
+            migrate_fallbacks: false,
+            group: None,
         }
     }
 
@@ -40,34 +364,524 @@ impl Entry {
     pub fn new_with_target(target: &str, service: &str, username: &str) -> Entry {
         Entry {
             target: credential::default_target(&platform(), Some(target), service, username),
+            access_log: None,
+            config: Config::new(),
+            tag: None,
+            fallbacks: Vec::new(),
+            migrate_fallbacks: false,
+            group: None,
         }
     }
 
     // Create an entry that uses the given credential for storage.  Callers can use
     // their own algorithm to produce a platform-specific credential spec for the
     // given service and username and then call this entry with that value.
+    // Since the credential is given in full, setting the password writes all
+    // of its attributes (see `Config::with_reset_attributes`).
     pub fn new_with_credential(target: &PlatformCredential) -> Result<Entry> {
         if target.matches_platform(&platform()) {
             Ok(Entry {
                 target: target.clone(),
+                access_log: None,
+                config: Config::new().with_reset_attributes(true),
+                tag: None,
+                fallbacks: Vec::new(),
+                migrate_fallbacks: false,
+                group: None,
             })
         } else {
             Err(Error::WrongCredentialPlatform)
         }
     }
 
+    // Create an entry for the credential that the given alias maps to (see
+    // `set_alias`), as with `new_with_credential`.  Fails with
+    // `UnknownAlias` if there's no such alias.
+    pub fn by_alias(alias: &str) -> Result<Entry> {
+        Entry::new_with_credential(&alias::resolve(alias)?)
+    }
+
+    // Delete the credentials (in the platform's default store, see `search`)
+    // whose service starts with the given prefix, returning the ones that
+    // were deleted.  Each deletion must be confirmed by the confirmation
+    // hook, if there is one; credentials it declines are left in place
+    // and aren't returned.  Any access logs of the deleted credentials
+    // are deleted as well.
+    pub fn delete_matching(prefix: &str) -> Result<Vec<PlatformCredential>> {
+        let mut deleted = Vec::new();
+        for credential in Entry::delete_matching_dry_run(prefix)? {
+            if Entry::delete_listed(&credential)? {
+                deleted.push(credential)
+            }
+        }
+        Ok(deleted)
+    }
+
+    // Delete the credentials that `delete_matching` would, but carry on
+    // past credentials that fail to be deleted, reporting which were
+    // deleted and which failed (with their errors).  After each deletion,
+    // `progress` is called with the deleted credential and the number
+    // deleted so far.  Only listing the credentials can fail outright.
+    pub fn delete_matching_with_progress(
+        prefix: &str,
+        mut progress: impl FnMut(&PlatformCredential, usize),
+    ) -> Result<DeletionReport> {
+        let mut report = DeletionReport::default();
+        for credential in Entry::delete_matching_dry_run(prefix)? {
+            match Entry::delete_listed(&credential) {
+                Ok(true) => {
+                    report.deleted.push(credential);
+                    progress(report.deleted.last().unwrap(), report.deleted.len());
+                }
+                Ok(false) => continue,
+                Err(err) => report.failed.push((credential, err)),
+            }
+        }
+        Ok(report)
+    }
+
+    // Delete a listed credential and its access log, taking it out of its
+    // group (if it's in one), returning whether it was deleted: it isn't
+    // if the confirmation hook declines, or if someone else deleted it
+    // since it was listed.  Once it's deleted, failures to clean up after
+    // it are ignored.
+    fn delete_listed(credential: &PlatformCredential) -> Result<bool> {
+        match Entry::new_with_credential(credential)?.delete_password() {
+            Ok(()) => {
+                let _ = access_log::delete(credential);
+                let _ = group::leave_any(credential);
+                Ok(true)
+            }
+            Err(Error::Cancelled) | Err(Error::NoEntry) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    // The credentials that `delete_matching` would consider deleting
+    // for the given prefix.  Nothing is deleted, and the confirmation
+    // hook isn't consulted.
+    pub fn delete_matching_dry_run(prefix: &str) -> Result<Vec<PlatformCredential>> {
+        Ok(platform::list_credentials()?
+            .into_iter()
+            .filter(|credential| credential.service().starts_with(prefix))
+            .collect())
+    }
+
+    // Create an entry for the same service as this one (with the same
+    // configuration, access logging and fallbacks), but for a different
+    // username; the fallbacks are changed to the new username as well.
+    // This is the entry you would get by creating one the same way as this
+    // one, but with the new username: in particular, a Windows entry with
+    // an explicit target keeps its target name.  For an entry created from
+    // a credential, only the username and the parts of the credential that
+    // the default mapper derives from it are changed.
+    pub fn clone_with_username(&self, username: &str) -> Entry {
+        Entry {
+            target: self.target.with_username(username),
+            access_log: self.access_log.clone(),
+            config: self.config.clone(),
+            tag: self.tag.clone(),
+            fallbacks: self
+                .fallbacks
+                .iter()
+                .map(|fallback| fallback.clone_with_username(username))
+                .collect(),
+            migrate_fallbacks: self.migrate_fallbacks,
+            group: self.group.clone(),
+        }
+    }
+
+    // A view of this entry for a single request (or other unit of work),
+    // whose operations' errors are wrapped in a `WithContext` error
+    // carrying the given tag.  The tag is only ever used in errors,
+    // so it mustn't be a secret.
+    pub fn with_tag(&self, tag: &str) -> Entry {
+        Entry {
+            target: self.target.clone(),
+            access_log: self.access_log.clone(),
+            config: self.config.clone(),
+            tag: Some(tag.to_string()),
+            fallbacks: self.fallbacks.clone(),
+            migrate_fallbacks: self.migrate_fallbacks,
+            group: self.group.clone(),
+        }
+    }
+
+    // A view of this entry that reads its password from the given
+    // fallback entries, in order, when this entry has none; for instance,
+    // entries for the names a service was stored under by older versions
+    // of an application.  Only reads of the password (`get_password`, and
+    // so `verify_password`) fall back: everything else, including setting
+    // and deleting the password, uses just this entry.
+    pub fn with_fallbacks(&self, fallbacks: Vec<Entry>) -> Entry {
+        Entry {
+            fallbacks,
+            ..self.clone()
+        }
+    }
+
+    // Copy a password read from a fallback (see `with_fallbacks`) to this
+    // entry, so that later reads find it here.  The fallback's credential
+    // is left in place.
+    pub fn with_fallback_migration(mut self) -> Entry {
+        self.migrate_fallbacks = true;
+        self
+    }
+
+    // Put this entry in the named group, so that its credential is deleted
+    // along with the rest of the group by `delete_group`.  The credential
+    // joins the group when its password is set through this entry, and
+    // leaves it when its password is deleted.  Group names are recorded in
+    // secure storage next to the credentials, so they mustn't be secrets.
+    pub fn with_group(mut self, group: &str) -> Entry {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    // Turn on access logging for this entry.  Each successful retrieval
+    // of the password through this entry appends a record (with the given
+    // tag) to the credential's access log, which keeps at most `capacity`
+    // of the most recent records.  On platforms that limit the length of
+    // a password, the capacity is lowered to the number of records that
+    // fit.  If the record can't be written, the retrieval fails.
+    pub fn with_access_log(mut self, capacity: usize, tag: Option<&str>) -> Entry {
+        self.access_log = Some(AccessLog::new(capacity, tag));
+        self
+    }
+
+    // Read back the access log for this item, oldest record first.  This
+    // doesn't retrieve the password, so it doesn't add to the log.
+    pub fn access_log(&self) -> Result<Vec<AccessRecord>> {
+        self.tagged(|| access_log::read(&self.target))
+    }
+
     // Set the password for this item.  Any other platform-specific
     // annotations are determined by the mapper that was used
-    // to create the credential.
+    // to create the credential.  If a policy is configured, the
+    // password must satisfy it.  If the credential already exists,
+    // the attributes its stored version has that the mapper doesn't
+    // determine are preserved: a Linux item's label and extra
+    // attributes, a Windows credential's comment and target alias, and
+    // a Mac item's comment and codes.  Entries configured to reset
+    // attributes (see `Config::with_reset_attributes`) replace them
+    // with the mapper's instead (except on Mac).
     pub fn set_password(&self, password: &str) -> Result<()> {
-        platform::set_password(&self.target, password)
+        self.tagged(|| {
+            self.injected_error()?;
+            let password = self.storable(password)?;
+            let target = self.writable_target()?;
+            self.invalidate_cached_password()?;
+            self.clear_companions(&[])?;
+            self.limited(|| platform::set_password(&target, &password))?;
+            absence::forget(&self.target);
+            self.join_group()
+        })
+    }
+
+    // Set the password for this item, noting the given expiry where the
+    // user can see it in the platform's UI: at the end of the credential's
+    // comment on Windows, and of the item's comment on Mac, as `expires `
+    // followed by the ISO-8601 UTC time (replacing any earlier such note).
+    // On Windows, a comment too long to take the note is cut short to
+    // make room for it.  Linux items have no comment, so there the expiry
+    // is ignored.  The expiry can be read back in the credential's
+    // metadata (see `get_metadata`).  Setting the password again without
+    // an expiry leaves the note in place, as part of the preserved
+    // comment (see `set_password`); on Windows, resetting attributes
+    // removes it.
+    pub fn set_password_with_expiry(&self, password: &str, expires: SystemTime) -> Result<()> {
+        self.tagged(|| {
+            self.injected_error()?;
+            let password = self.storable(password)?;
+            let target = self.writable_target()?.with_expiry(expires);
+            self.invalidate_cached_password()?;
+            self.clear_companions(&[])?;
+            self.limited(|| platform::set_password(&target, &password))?;
+            absence::forget(&self.target);
+            self.join_group()
+        })
+    }
+
+    // Set the password for this item, along with an access schedule giving
+    // when it may be read.  The schedule is enforced by entries configured
+    // to enforce schedules (see `Config::with_enforced_schedules`): they
+    // fail to read the password outside it with `OutsideSchedule`.  The
+    // schedule is stored before the password, so the password is never
+    // stored without it.
+    pub fn set_password_with_schedule(
+        &self,
+        password: &str,
+        schedule: AccessSchedule,
+    ) -> Result<()> {
+        self.tagged(|| {
+            self.injected_error()?;
+            let password = self.storable(password)?;
+            let target = self.writable_target()?;
+            self.limited(|| schedule::write(&self.target, &schedule))?;
+            self.invalidate_cached_password()?;
+            self.clear_companions(&[schedule::PURPOSE])?;
+            self.limited(|| platform::set_password(&target, &password))?;
+            absence::forget(&self.target);
+            self.join_group()
+        })
+    }
+
+    // The access schedule stored with this item's password, if there is one.
+    pub fn schedule(&self) -> Result<Option<AccessSchedule>> {
+        self.tagged(|| self.limited(|| schedule::read(&self.target)))
+    }
+
+    // Set the password for this item, along with the context (such as
+    // `prod-db`) it's meant to be used in, which `get_password_for_context`
+    // checks.  The context is stored before the password, so the password
+    // is never stored without it.
+    pub fn set_password_with_context(&self, password: &str, context: &str) -> Result<()> {
+        self.tagged(|| {
+            self.injected_error()?;
+            let password = self.storable(password)?;
+            let target = self.writable_target()?;
+            self.limited(|| context::write(&self.target, context))?;
+            self.invalidate_cached_password()?;
+            self.clear_companions(&[context::PURPOSE])?;
+            self.limited(|| platform::set_password(&target, &password))?;
+            absence::forget(&self.target);
+            self.join_group()
+        })
+    }
+
+    // Retrieve the password for this item, failing with `ContextMismatch`
+    // if it was stored for a context other than the given one.  A password
+    // stored without a context is retrieved for any context.
+    pub fn get_password_for_context(&self, context: &str) -> Result<String> {
+        self.tagged(|| match self.context()? {
+            Some(stored) if stored != context => {
+                Err(Error::ContextMismatch(stored, context.to_string()))
+            }
+            _ => self.get_password(),
+        })
+    }
+
+    // The context stored with this item's password, if there is one.
+    pub fn context(&self) -> Result<Option<String>> {
+        self.tagged(|| self.limited(|| context::read(&self.target)))
+    }
+
+    // Hand off a secret to another process through this entry (see the
+    // `handoff` module): the secret is stored, encrypted under the
+    // returned token, for the process given the token to read once with
+    // `read_handoff`.  This replaces any earlier handoff through this
+    // entry that hasn't been read yet.  It doesn't touch the password.
+    #[cfg(feature = "handoff")]
+    pub fn set_handoff(&self, secret: &str) -> Result<HandoffToken> {
+        self.tagged(|| {
+            self.injected_error()?;
+            self.limited(|| handoff::set(&self.target, secret))
+        })
+    }
+
+    // Read the secret handed off through this entry with the given token,
+    // deleting it.  Fails with `NoEntry` if there is no handoff (or it
+    // has been read already), and with `BadHandoffToken` if the token
+    // isn't the one it was handed off with.
+    #[cfg(feature = "handoff")]
+    pub fn read_handoff(&self, token: &HandoffToken) -> Result<String> {
+        self.tagged(|| {
+            self.injected_error()?;
+            self.limited(|| handoff::read(&self.target, token))
+        })
     }
 
     // Retrieve the password saved for this item.
     // Returns a `NoEntry` error is there isn't one.
+    // If the entry uses a cache file, a fresh cached password is returned
+    // without reading secure storage.  If the entry remembers absences (see
+    // `Config::with_absence_ttl`), a credential recently found to be absent
+    // isn't looked for again.  If the entry has fallbacks (see
+    // `with_fallbacks`), the first of them with a password is read.  If
+    // none of them has one either, and there is a provisioner (see
+    // `Config::with_provisioner`), the password it supplies is stored
+    // and returned.
     pub fn get_password(&self) -> Result<String> {
-        let mut map = self.target.clone();
-        platform::get_password(&mut map)
+        self.tagged(|| match self.read_password() {
+            Err(Error::NoEntry) => {
+                for fallback in &self.fallbacks {
+                    match fallback.get_password() {
+                        Ok(password) => {
+                            if self.migrate_fallbacks {
+                                self.set_password(&password)?;
+                            }
+                            return Ok(password);
+                        }
+                        Err(err) if matches!(err.inner(), Error::NoEntry) => continue,
+                        Err(err) => return Err(err),
+                    }
+                }
+                match self.config.provision(&self.target) {
+                    Some(password) => {
+                        self.set_password(&password)?;
+                        Ok(password)
+                    }
+                    None => Err(Error::NoEntry),
+                }
+            }
+            result => result,
+        })
+    }
+
+    fn read_password(&self) -> Result<String> {
+        self.injected_error()?;
+        self.check_schedule()?;
+        let password = match self.cached_password() {
+            Some(password) => password,
+            None => {
+                self.check_absence()?;
+                let marker = self.encoding_marker()?;
+                let password = self.note_absence(self.on_credential(|target| {
+                    encoding::read_password(&mut target.clone(), marker)
+                }))?;
+                let password = self.check_corrupt(password)?;
+                let password = self
+                    .config
+                    .normalize_read(self.config.transform_load(password)?);
+                self.cache_password(&password);
+                password
+            }
+        };
+        self.record_access()?;
+        Ok(password)
+    }
+
+    // Read the password saved for this item (as with `get_password`), but
+    // give up waiting for it at the deadline.  Returns the bytes read and
+    // whether the read completed.  The bytes are all or nothing: a read
+    // that completed returns the whole password, and one that didn't
+    // complete in time returns no bytes, and is left to finish in the
+    // background, its result discarded.
+    //
+    // The read is made with user interaction turned off, so on Mac a read
+    // that would prompt fails with `NoInteraction` rather than leaving a
+    // dialog up after the deadline.  The Mac can only turn interaction off
+    // for the whole process, so while the read runs (including after the
+    // deadline, until an abandoned read finishes, however long that takes)
+    // any other thread's operation that needs a prompt fails with
+    // `NoInteraction` too.  Don't use this while other threads may need
+    // to prompt the user.  The secret service prompts to unlock a
+    // collection on its own terms, so on Linux a prompt can still appear
+    // after the deadline has passed.
+    pub fn read_secret_until(&self, deadline: Instant) -> Result<(Vec<u8>, bool)> {
+        let timeout = match deadline.checked_duration_since(Instant::now()) {
+            Some(timeout) if !timeout.is_zero() => timeout,
+            _ => return Ok((Vec::new(), false)),
+        };
+        let (sender, receiver) = mpsc::channel();
+        let entry = self.clone();
+        let reader = thread::spawn(move || {
+            // the receiver may have given up already
+            let _ = sender.send(platform::without_interaction(|| entry.get_password()));
+        });
+        match receiver.recv_timeout(timeout) {
+            Ok(password) => Ok((password?.into_bytes(), true)),
+            Err(RecvTimeoutError::Timeout) => Ok((Vec::new(), false)),
+            Err(RecvTimeoutError::Disconnected) => match reader.join() {
+                Err(panic) => std::panic::resume_unwind(panic),
+                Ok(()) => unreachable!("the reader always sends a result"),
+            },
+        }
+    }
+
+    // Check whether the given password matches the one saved for this
+    // item, after normalizing both (see `Normalization`).  Returns a `NoEntry`
+    // error if there isn't one.  A successful check counts as a retrieval
+    // (for access logging) since it reveals whether the guess is right.
+    pub fn verify_password(&self, candidate: &str) -> Result<bool> {
+        self.tagged(|| {
+            let password = self.get_password()?;
+            Ok(constant_time_eq(
+                password.as_bytes(),
+                self.config.normalize(candidate).as_bytes(),
+            ))
+        })
+    }
+
+    // Set the password for this item, stored in the given encoding rather
+    // than the platform's, with a marker recording the encoding.  Entries
+    // configured to use encoding markers (see
+    // `Config::with_encoding_markers`) decode the password in that
+    // encoding whenever they read it; other entries decode the platform's
+    // encoding.  A password with characters the encoding lacks fails with
+    // `BadFormat`.  The marker is stored before the password, so the
+    // password is never stored without it.
+    pub fn set_password_encoded(&self, password: &str, encoding: Encoding) -> Result<()> {
+        self.tagged(|| {
+            self.injected_error()?;
+            let password = self.storable(password)?;
+            let mut secret = encoding::encode(&password, encoding)?;
+            let target = self.writable_target()?;
+            self.limited(|| encoding::write_marker(&self.target, encoding))?;
+            self.invalidate_cached_password()?;
+            self.clear_companions(&[encoding::PURPOSE])?;
+            let stored = self.limited(|| platform::set_secret(&target, &secret));
+            zeroing::wipe(&mut secret);
+            stored?;
+            absence::forget(&self.target);
+            self.join_group()
+        })
+    }
+
+    // The encoding this item's password was stored in by
+    // `set_password_encoded`, if it was stored that way: passwords stored
+    // otherwise, including by other tools, have no marker.
+    pub fn stored_encoding(&self) -> Result<Option<Encoding>> {
+        self.tagged(|| self.limited(|| encoding::read_marker(&self.target)))
+    }
+
+    // Retrieve the password saved for this item (as with `get_password`),
+    // guessing its encoding from the stored bytes (see `Encoding`): this
+    // is for reading passwords written by other tools, which may not
+    // have used the platform's encoding.  The password is returned with
+    // the encoding it was decoded as.  A password that doesn't decode
+    // cleanly in any of the encodings fails with `BadEncoding`.  If this
+    // entry uses encoding markers, a password stored with one is decoded
+    // in the marked encoding instead.
+    pub fn get_password_detect(&self) -> Result<(String, Encoding)> {
+        if let Some(encoding) = self.tagged(|| self.encoding_marker())? {
+            let bytes = self.get_raw()?;
+            return self.tagged(|| match encoding::decode(&bytes, encoding) {
+                Some(password) => {
+                    let password = self.config.transform_load(password)?;
+                    Ok((self.config.normalize_read(password), encoding))
+                }
+                None => Err(Error::BadEncoding(bytes)),
+            });
+        }
+        let bytes = match self.get_password() {
+            Ok(password) => encoding::stored_bytes(&password),
+            Err(err) => match err.inner() {
+                Error::BadEncoding(bytes) => bytes.clone(),
+                _ => return Err(err),
+            },
+        };
+        self.tagged(|| match encoding::detect(&bytes) {
+            Some((password, encoding)) => Ok((self.config.normalize_read(password), encoding)),
+            None => Err(Error::BadEncoding(bytes)),
+        })
+    }
+
+    // Retrieve the secret saved for this item exactly as the platform
+    // stores it, for diagnosing why a password reads differently than
+    // expected: it's never decoded (the bytes are UTF-16LE on Windows,
+    // and normally UTF-8 elsewhere), normalized, transformed (see
+    // `Config::with_transform`), or taken from a cache file, whatever
+    // this entry's or the global configuration.  Access schedules and
+    // logging still apply, since reading the bytes reveals the password.
+    pub fn get_raw(&self) -> Result<Vec<u8>> {
+        self.tagged(|| {
+            self.injected_error()?;
+            self.check_schedule()?;
+            let secret = self.on_credential(|target| platform::get_secret(&mut target.clone()))?;
+            self.record_access()?;
+            Ok(secret)
+        })
     }
 
     // Retrieve the password and all the other fields
@@ -75,16 +889,426 @@ impl Entry {
     // allows retrieving metadata on the credential that
     // were saved by external applications.
     pub fn get_password_and_credential(&self) -> Result<(String, PlatformCredential)> {
-        let mut map = self.target.clone();
-        let password = platform::get_password(&mut map)?;
-        Ok((password, map))
+        self.tagged(|| {
+            self.injected_error()?;
+            self.check_schedule()?;
+            self.check_absence()?;
+            let marker = self.encoding_marker()?;
+            let (password, map) = self.note_absence(self.on_credential(|target| {
+                let mut map = target.clone();
+                encoding::read_password(&mut map, marker).map(|password| (password, map))
+            }))?;
+            let password = self.check_corrupt(password)?;
+            self.record_access()?;
+            let password = self.config.transform_load(password)?;
+            Ok((self.config.normalize_read(password), map))
+        })
+    }
+
+    // Export the credential, with its password, as the command that
+    // stores it with the platform's own tool (see `NativeExport`).  The
+    // password is read as with `get_password_and_credential`, so the
+    // export has the attributes the credential is actually stored with.
+    pub fn export_native(&self) -> Result<NativeExport> {
+        let (mut password, credential) = self.get_password_and_credential()?;
+        let exported = native::export(&credential, &password);
+        zeroing::wipe(&mut password);
+        Ok(exported)
+    }
+
+    // Retrieve the password, the platform-specific credential (as with
+    // `get_password_and_credential`), and the metadata the platform keeps
+    // about it (as with `get_metadata`).  On Windows and Linux these all
+    // come from a single read of the credential, so the metadata always
+    // describes the password that was read.
+    pub fn get_password_credential_and_metadata(
+        &self,
+    ) -> Result<(String, PlatformCredential, CredentialMetadata)> {
+        self.tagged(|| {
+            self.injected_error()?;
+            self.check_schedule()?;
+            self.check_absence()?;
+            let (password, map, metadata) = self.note_absence(self.on_credential(|target| {
+                let mut map = target.clone();
+                platform::get_password_and_metadata(&mut map)
+                    .map(|(password, metadata)| (password, map, metadata))
+            }))?;
+            self.record_access()?;
+            let password = self.config.transform_load(password)?;
+            Ok((self.config.normalize_read(password), map, metadata))
+        })
+    }
+
+    // Retrieve the metadata the platform keeps about this item's
+    // credential, such as when it was last modified.  This doesn't
+    // retrieve the password.
+    pub fn get_metadata(&self) -> Result<CredentialMetadata> {
+        self.tagged(|| {
+            self.injected_error()?;
+            self.on_credential(platform::get_metadata)
+        })
+    }
+
+    // Whether this item has a password.  This doesn't retrieve the password
+    // (so it doesn't count as an access for access logging).
+    pub fn exists(&self) -> Result<bool> {
+        self.tagged(|| match self.get_metadata() {
+            Ok(_) => Ok(true),
+            Err(err) if matches!(err.inner(), Error::NoEntry) => Ok(false),
+            Err(err) => Err(err),
+        })
+    }
+
+    // How many more bytes this item's password could take up in its store
+    // before a write would fail with `TooLong`, or `None` where the store
+    // has no such limit (Linux and Mac).  On Windows the limit is that of
+    // the credential blob (2560 bytes), where a password takes two bytes
+    // per UTF-16 code unit, less what the current secret (if any) takes
+    // up, whatever its encoding.  The secret is read (without access
+    // logging, and without decoding it) only where there's a limit.
+    pub fn remaining_capacity(&self) -> Result<Option<usize>> {
+        self.tagged(|| {
+            self.injected_error()?;
+            let capacity = match platform::password_capacity() {
+                Some(capacity) => capacity,
+                None => return Ok(None),
+            };
+            let stored = match self.stored_secret() {
+                Ok(secret) => secret.len(),
+                Err(Error::NoEntry) => 0,
+                Err(err) => return Err(err),
+            };
+            Ok(Some(capacity.saturating_sub(stored)))
+        })
+    }
+
+    // Whether setting this item's password would be allowed by its store
+    // right now: on Linux, whether its collection is unlocked; on Mac,
+    // whether its keychain is unlocked and writable; on Windows, whether
+    // the logon session allows storing credentials.  This is a probe
+    // that never writes (or unlocks) anything.  A store that would have
+    // to be unlocked first counts as not writable; a state that can't be
+    // determined (say, there is no secret service) is an error.  It
+    // doesn't account for the confirmation hook or policy.
+    pub fn writable(&self) -> Result<bool> {
+        self.tagged(|| {
+            self.injected_error()?;
+            platform::is_writable(&self.target)
+        })
+    }
+
+    // Retrieve the settings that control access to this item's credential
+    // (see `AccessControl` for what each platform reports).  This doesn't
+    // retrieve the password, and on Linux it doesn't unlock the collection.
+    pub fn access_control(&self) -> Result<AccessControl> {
+        self.tagged(|| {
+            self.injected_error()?;
+            self.on_credential(platform::get_access_control)
+        })
+    }
+
+    // The paths of the applications trusted to read this item's Mac keychain
+    // item without prompting (see `AccessControl`).  Other platforms don't
+    // have trusted applications, so there the list is always empty.  This
+    // doesn't retrieve the password.
+    pub fn trusted_applications(&self) -> Result<Vec<String>> {
+        self.tagged(|| {
+            Ok(self
+                .access_control()?
+                .trusted_applications
+                .unwrap_or_default())
+        })
+    }
+
+    // The application that created this item's credential, where the
+    // platform records it: on Mac, the path of the first application
+    // trusted by the item's access control lists (the keychain trusts
+    // the creating application when it adds an item); on Linux, the
+    // item's `application` attribute, which this crate and some other
+    // applications set (to `rust-keyring`, for this crate).  Windows
+    // doesn't record it, so there (and where it isn't recorded) this is
+    // `None`.  This doesn't retrieve the password.
+    pub fn creating_app(&self) -> Result<Option<String>> {
+        self.tagged(|| match &self.target {
+            PlatformCredential::Mac(_) => Ok(self.trusted_applications()?.into_iter().next()),
+            PlatformCredential::Linux(_) => {
+                self.injected_error()?;
+                let stored = self.limited(|| {
+                    let mut stored = self.target.clone();
+                    platform::get_attributes(&mut stored).map(|()| stored)
+                })?;
+                match stored {
+                    PlatformCredential::Linux(cred) => {
+                        Ok(cred.attributes.get("application").cloned())
+                    }
+                    _ => Ok(None),
+                }
+            }
+            PlatformCredential::Win(_) => self.get_metadata().map(|_| None),
+        })
+    }
+
+    // The identifier of the backend that services this entry's operations
+    // (see `PlatformCredential::backend_name`).  This is resolved from the
+    // entry's target, so it reflects an explicit credential given with
+    // `new_with_credential` as well as the platform the crate was built for.
+    pub fn backend_name(&self) -> &'static str {
+        self.target.backend_name()
+    }
+
+    // Whether this item's credential roams (or syncs) to the user's other
+    // devices: a synchronizable item on Mac, or a credential with enterprise
+    // persistence on Windows.  Linux credentials never roam.  Like
+    // `access_control`, this doesn't retrieve the password.
+    pub fn is_roaming(&self) -> Result<bool> {
+        self.tagged(|| {
+            let access = self.access_control()?;
+            Ok(access.synchronizable == Some(true)
+                || access.persistence == Some(WinPersistence::Enterprise))
+        })
     }
 
     // Delete the password for this item.  (Although the item
     // itself follows the Rust structure lifecycle, deleting
     // the password deletes the platform credential from secure storage.)
+    // If access logging is on for this entry, the access log is deleted too,
+    // as are any access schedule and context, and any encoding marker if
+    // the entry uses encoding markers, and if the entry is in a group, its
+    // credential leaves the group.  Once the password is deleted, failures
+    // to delete these are ignored.
+    // If a confirmation hook is configured, it must confirm the deletion.
     pub fn delete_password(&self) -> Result<()> {
-        platform::delete_password(&self.target)
+        self.tagged(|| {
+            self.injected_error()?;
+            if !self.config.confirm_delete(&self.target) {
+                return Err(Error::Cancelled);
+            }
+            self.invalidate_cached_password()?;
+            self.on_credential(platform::delete_password)?;
+            // the password is gone, so failing to clean up after it isn't
+            // a failure to delete it
+            if self.access_log.is_some() {
+                let _ = access_log::delete(&self.target);
+            }
+            let _ = self.clear_companions(&[]);
+            if let Some(group) = &self.group {
+                let _ = group::leave(group, &self.target);
+            }
+            Ok(())
+        })
+    }
+
+    // Run an operation, wrapping any error in this entry's tag (if it has
+    // one).  Errors from operations nested inside others are only wrapped once.
+    fn tagged<T>(&self, operation: impl FnOnce() -> Result<T>) -> Result<T> {
+        operation().map_err(|err| match (&self.tag, err) {
+            (Some(tag), err) if !matches!(err, Error::WithContext(..)) => {
+                Error::WithContext(tag.clone(), Box::new(err))
+            }
+            (_, err) => err,
+        })
+    }
+
+    // With the `mock` feature, fail with an error injected for this operation.
+    #[cfg(any(test, feature = "mock"))]
+    fn injected_error(&self) -> Result<()> {
+        mock::take_error(&self.target).map_or(Ok(()), Err)
+    }
+
+    #[cfg(not(any(test, feature = "mock")))]
+    fn injected_error(&self) -> Result<()> {
+        Ok(())
+    }
+
+    // Run an operation on this entry's credential.  If the credential isn't
+    // found and this entry ignores the case of usernames, run it again on
+    // the listed credential that differs only in the case of its username,
+    // if there is one.
+    fn on_credential<T>(&self, operation: impl Fn(&PlatformCredential) -> Result<T>) -> Result<T> {
+        match self.limited(|| operation(&self.target)) {
+            Err(Error::NoEntry) if self.config.case_insensitive_usernames() => {
+                match self
+                    .limited(platform::list_credentials)?
+                    .into_iter()
+                    .find(|credential| self.target.matches_ignoring_username_case(credential))
+                {
+                    Some(credential) => self.limited(|| operation(&credential)),
+                    None => Err(Error::NoEntry),
+                }
+            }
+            result => result,
+        }
+    }
+
+    // Run an operation on secure storage, limiting how often it may prompt
+    // the user if this entry is configured to (see `prompt`), and retrying
+    // it if it fails with a transient error and the entry is configured to.
+    fn limited<T>(&self, operation: impl Fn() -> Result<T>) -> Result<T> {
+        let (retries, delay) = self.config.transient_retries();
+        let mut attempts = 0;
+        loop {
+            match prompt::limited(self.config.prompt_interval(), &operation) {
+                Err(err) if attempts < retries && err.is_transient() => {
+                    attempts += 1;
+                    thread::sleep(delay);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    // Remove the companions stored with the password being replaced (or
+    // deleted), which describe that password rather than the credential:
+    // its access schedule and context, and its encoding marker if this
+    // entry uses encoding markers.  A write that stores a new one of these
+    // names it in `keep`.
+    fn clear_companions(&self, keep: &[&str]) -> Result<()> {
+        if !keep.contains(&schedule::PURPOSE) {
+            self.limited(|| schedule::delete(&self.target))?;
+        }
+        if !keep.contains(&context::PURPOSE) {
+            self.limited(|| context::delete(&self.target))?;
+        }
+        if self.config.encoding_markers() && !keep.contains(&encoding::PURPOSE) {
+            self.limited(|| encoding::delete_marker(&self.target))?;
+        }
+        Ok(())
+    }
+
+    // The encoding marker stored with this item's password, if this entry
+    // uses encoding markers and the password has one.
+    fn encoding_marker(&self) -> Result<Option<Encoding>> {
+        if !self.config.encoding_markers() {
+            return Ok(None);
+        }
+        self.limited(|| encoding::read_marker(&self.target))
+    }
+
+    // The credential to write when setting this item's password: the
+    // entry's own, preserving the attributes of the stored version, if
+    // there is one and the entry doesn't reset attributes.  Its store is
+    // created first, if it's missing and the entry's collection policy
+    // says to create it.
+    fn writable_target(&self) -> Result<PlatformCredential> {
+        if self.config.collection_policy() == CollectionPolicy::CreateIfMissing {
+            self.limited(|| platform::ensure_store(&self.target))?;
+        }
+        if self.config.reset_attributes() {
+            return Ok(self.target.clone());
+        }
+        let stored = self.limited(|| {
+            let mut stored = self.target.clone();
+            platform::get_attributes(&mut stored).map(|()| stored)
+        });
+        match stored {
+            Ok(stored) => Ok(self.target.preserving(&stored)),
+            Err(Error::NoEntry) => Ok(self.target.clone()),
+            Err(err) => Err(err),
+        }
+    }
+
+    // What to store when setting the given password: the password
+    // normalized, checked against the policy (if there is one), and
+    // transformed (if there is a transform).
+    fn storable(&self, password: &str) -> Result<String> {
+        let password = self.config.normalize(password);
+        self.config
+            .check_policy(password)
+            .map_err(Error::PolicyViolation)?;
+        self.config.transform_store(password)
+    }
+
+    // The secret stored for this item, exactly as the platform stores it
+    // (as `get_raw` reads it), but without the checks and logging of a
+    // retrieval, so it can be put back as it was (see `Transaction`).
+    pub(crate) fn stored_secret(&self) -> Result<Vec<u8>> {
+        self.on_credential(|target| platform::get_secret(&mut target.clone()))
+    }
+
+    // Store a secret read with `stored_secret` back as it was, without
+    // normalizing, checking or transforming it.
+    pub(crate) fn restore_secret(&self, secret: &[u8]) -> Result<()> {
+        let target = self.writable_target()?;
+        self.invalidate_cached_password()?;
+        self.limited(|| platform::set_secret(&target, secret))?;
+        absence::forget(&self.target);
+        Ok(())
+    }
+
+    // Fail with `OutsideSchedule` if this entry enforces access schedules
+    // and its password has a schedule that doesn't allow reading it now.
+    fn check_schedule(&self) -> Result<()> {
+        if !self.config.enforced_schedules() {
+            return Ok(());
+        }
+        match self.limited(|| schedule::read(&self.target))? {
+            Some(schedule) if !schedule.allows(self.config.now()) => Err(Error::OutsideSchedule),
+            _ => Ok(()),
+        }
+    }
+
+    // Fail with `CorruptCredential` if the password read from secure
+    // storage is empty and this entry takes that to be corrupt.
+    fn check_corrupt(&self, stored: String) -> Result<String> {
+        if stored.is_empty() && self.config.empty_as_corrupt() {
+            return Err(Error::CorruptCredential);
+        }
+        Ok(stored)
+    }
+
+    // Fail with `NoEntry` if this entry remembers absences and its
+    // credential was recently found to be absent.
+    fn check_absence(&self) -> Result<()> {
+        if self.config.absence_ttl().is_some() && absence::is_known_absent(&self.target) {
+            return Err(Error::NoEntry);
+        }
+        Ok(())
+    }
+
+    // If this entry remembers absences, remember a read that found no entry.
+    fn note_absence<T>(&self, result: Result<T>) -> Result<T> {
+        if let (Err(Error::NoEntry), Some(ttl)) = (&result, self.config.absence_ttl()) {
+            absence::remember(&self.target, ttl);
+        }
+        result
+    }
+
+    fn cached_password(&self) -> Option<String> {
+        #[cfg(feature = "cache-file")]
+        if let Some(cache) = self.config.cache_file() {
+            return cache.get(&self.target);
+        }
+        None
+    }
+
+    #[cfg_attr(not(feature = "cache-file"), allow(unused_variables))]
+    fn cache_password(&self, password: &str) {
+        #[cfg(feature = "cache-file")]
+        if let Some(cache) = self.config.cache_file() {
+            cache.put(&self.target, password);
+        }
+    }
+
+    fn invalidate_cached_password(&self) -> Result<()> {
+        #[cfg(feature = "cache-file")]
+        if let Some(cache) = self.config.cache_file() {
+            cache.invalidate(&self.target)?;
+        }
+        Ok(())
+    }
+
+    fn join_group(&self) -> Result<()> {
+        match &self.group {
+            Some(group) => group::join(group, &self.target),
+            None => Ok(()),
+        }
+    }
+
+    fn record_access(&self) -> Result<()> {
+        match &self.access_log {
+            Some(log) => log.record(&self.target),
+            None => Ok(()),
+        }
     }
 }
 
@@ -122,6 +1346,124 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_clone_with_username() {
+        let name = generate_random_string();
+        let other = generate_random_string();
+        let entry = Entry::new(&name, &name).with_access_log(3, None);
+        let clone = entry.clone_with_username(&other);
+        assert_eq!(
+            clone.target,
+            default_target(&platform(), None, &name, &other)
+        );
+        assert_eq!(clone.access_log, entry.access_log);
+        // every platform's credential, with and without a target
+        for os in [Platform::Linux, Platform::Windows, Platform::MacOs] {
+            for target in [None, Some(name.as_str())] {
+                let credential = default_target(&os, target, &name, &name);
+                let expected = default_target(&os, target, &name, &other);
+                assert_eq!(credential.with_username(&other), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_delete_matching_with_progress() {
+        let prefix = generate_random_string();
+        for suffix in ["one", "two", "three"] {
+            Entry::new(&format!("{}-{}", prefix, suffix), "user")
+                .set_password("bulk")
+                .unwrap();
+        }
+        let mut counts = Vec::new();
+        // the first deletion fails, but the others go ahead
+        mock::inject_error_next(Error::NoInteraction);
+        let report = Entry::delete_matching_with_progress(&prefix, |credential, count| {
+            assert!(credential.service().starts_with(&prefix));
+            counts.push(count);
+        })
+        .unwrap();
+        assert_eq!(counts, vec![1, 2]);
+        assert_eq!(report.deleted.len(), 2);
+        assert_eq!(report.failed.len(), 1);
+        assert!(matches!(report.failed[0].1, Error::NoInteraction));
+        let remaining = Entry::delete_matching(&prefix).unwrap();
+        assert_eq!(remaining, vec![report.failed[0].0.clone()]);
+    }
+
+    #[test]
+    fn test_tagged_errors() {
+        let entry = Entry::new("tagged", "user");
+        let tagged = entry.with_tag("request 17");
+        mock::inject_error_next(Error::NoEntry);
+        let err = tagged.is_roaming().unwrap_err();
+        assert_eq!(err.tag(), Some("request 17"));
+        assert!(matches!(err.inner(), Error::NoEntry));
+        assert!(
+            matches!(err, Error::WithContext(_, ref inner) if matches!(**inner, Error::NoEntry))
+        );
+        assert_eq!(
+            err.to_string(),
+            "[request 17] No matching entry found in secure storage"
+        );
+        mock::inject_error_next(Error::NoEntry);
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    }
+
+    #[test]
+    fn test_configured_store() {
+        let name = generate_random_string();
+        let entry = Entry::new_with_config(&name, &name, Config::new().with_store(&name));
+        // Windows has only one store
+        let expected_store = match platform() {
+            Platform::Windows => None,
+            _ => Some(name.as_str()),
+        };
+        let expected_target = default_target(&platform(), expected_store, &name, &name);
+        assert_eq!(entry.target, expected_target);
+    }
+
+    #[test]
+    fn test_absence_ttl() {
+        let name = generate_random_string();
+        let config = Config::new().with_absence_ttl(std::time::Duration::from_secs(60));
+        let entry = Entry::new_with_config(&name, &name, config);
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        // created behind the entry's back, so it's still remembered as absent
+        platform::set_password(&entry.target, "unseen").unwrap();
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        assert!(matches!(
+            entry.get_password_and_credential(),
+            Err(Error::NoEntry)
+        ));
+        // entries that don't remember absences aren't affected
+        assert_eq!(Entry::new(&name, &name).get_password().unwrap(), "unseen");
+        entry.set_password("seen").unwrap();
+        assert_eq!(entry.get_password().unwrap(), "seen");
+        entry.delete_password().unwrap();
+    }
+
+    #[test]
+    fn test_hashed_names() {
+        let config = Config::new().with_name_hasher(|name| format!("hashed-{}", name.len()));
+        let entry = Entry::new_with_config("service", "username", config);
+        let expected = default_target(&platform(), None, "hashed-7", "hashed-8");
+        assert_eq!(entry.target, expected);
+        assert_eq!(entry.target.username(), "hashed-8");
+    }
+
+    #[test]
+    fn test_backend_name() {
+        let expected = match platform() {
+            Platform::Linux => "secret-service",
+            Platform::Windows => "windows-credential-manager",
+            Platform::MacOs => "macos-keychain",
+        };
+        assert_eq!(Entry::new("service", "user").backend_name(), expected);
+        let mac = default_target(&Platform::MacOs, None, "service", "user");
+        assert_eq!(mac.backend_name(), "macos-keychain");
+    }
+
     fn generate_random_string() -> String {
         // from the Rust Cookbook:
         // https://rust-lang-nursery.github.io/rust-cookbook/algorithms/randomness.html
@@ -1,4 +1,5 @@
 use secret_service::{Collection, EncryptionType, Item, SecretService};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::{Error as ErrorCode, Platform, PlatformCredential, Result};
 
@@ -6,20 +7,84 @@ pub fn platform() -> Platform {
     Platform::Linux
 }
 
-use crate::credential::LinuxCredential;
+use crate::credential::{AccessControl, CredentialMetadata, LinuxCredential};
 pub use secret_service::Error;
 
+// The credential's collection, unlocked.
 fn get_collection<'a>(map: &LinuxCredential, ss: &'a SecretService) -> Result<Collection<'a>> {
-    let collection = ss
-        .get_collection_by_alias(map.collection.as_str())
-        .map_err(decode_error)?;
+    let collection = find_collection(map, ss)?;
     if collection.is_locked().map_err(decode_error)? {
         collection.unlock().map_err(decode_error)?;
     }
     Ok(collection)
 }
 
+// The credential's collection, locked or not.  A missing collection
+// fails with `NoSuchCollection`.
+fn find_collection<'a>(map: &LinuxCredential, ss: &'a SecretService) -> Result<Collection<'a>> {
+    match ss.get_collection_by_alias(map.collection.as_str()) {
+        Ok(collection) => Ok(collection),
+        Err(Error::NoResult) => Err(ErrorCode::NoSuchCollection(map.collection.clone())),
+        Err(err) => Err(decode_error(err)),
+    }
+}
+
+// Create the credential's collection, if it doesn't exist, with the
+// collection name as both its alias and its label.  Creating a
+// collection prompts the user (for the new collection's password).
+pub fn ensure_store(map: &PlatformCredential) -> Result<()> {
+    if let PlatformCredential::Linux(map) = map {
+        let ss = SecretService::new(EncryptionType::Dh).map_err(decode_error)?;
+        match find_collection(map, &ss) {
+            Err(ErrorCode::NoSuchCollection(_)) => {
+                ss.create_collection(&map.collection, &map.collection)
+                    .map_err(decode_error)?;
+                Ok(())
+            }
+            result => result.map(|_| ()),
+        }
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+// The secret service prompts (to unlock a collection) on its own terms,
+// so operations can't be kept from prompting; they are just run.
+pub fn without_interaction<T>(operation: impl FnOnce() -> Result<T>) -> Result<T> {
+    operation()
+}
+
+// Whether an error is one that may go away if the operation is retried.
+// There are no such errors on this platform.
+pub fn is_transient(_err: &ErrorCode) -> bool {
+    false
+}
+
+// The message that came with a D-Bus error from the secret service (or
+// the bus itself).  Other errors are raised by the secret-service crate,
+// so they have no message of the platform's.
+pub fn error_message(err: &Error) -> Option<String> {
+    match err {
+        Error::Zbus(err) => zbus_message(err),
+        Error::ZbusFdo(zbus::fdo::Error::ZBus(err)) => zbus_message(err),
+        Error::ZbusFdo(err) => Some(err.description().to_string()),
+        _ => None,
+    }
+}
+
+fn zbus_message(err: &zbus::Error) -> Option<String> {
+    match err {
+        zbus::Error::MethodError(_, detail, _) => detail.clone(),
+        _ => None,
+    }
+}
+
 pub fn set_password(map: &PlatformCredential, password: &str) -> Result<()> {
+    set_secret(map, password.as_bytes())
+}
+
+// Store the secret of a credential exactly as given.
+pub fn set_secret(map: &PlatformCredential, secret: &[u8]) -> Result<()> {
     if let PlatformCredential::Linux(map) = map {
         let ss = SecretService::new(EncryptionType::Dh).map_err(ErrorCode::PlatformFailure)?;
         let collection = get_collection(map, &ss)?;
@@ -27,7 +92,7 @@ pub fn set_password(map: &PlatformCredential, password: &str) -> Result<()> {
             .create_item(
                 map.label.as_str(),
                 map.attributes(),
-                password.as_bytes(),
+                secret,
                 true, // replace
                 "text/plain",
             )
@@ -38,20 +103,64 @@ pub fn set_password(map: &PlatformCredential, password: &str) -> Result<()> {
     }
 }
 
+// Whether the credential's collection is unlocked, so a password can be
+// stored in it without prompting.  This doesn't unlock it.
+pub fn is_writable(map: &PlatformCredential) -> Result<bool> {
+    if let PlatformCredential::Linux(map) = map {
+        let ss = SecretService::new(EncryptionType::Dh).map_err(decode_error)?;
+        let collection = find_collection(map, &ss)?;
+        Ok(!collection.is_locked().map_err(decode_error)?)
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+// Read the label and attributes of a stored item into the credential,
+// without reading its password.
+pub fn get_attributes(map: &mut PlatformCredential) -> Result<()> {
+    if let PlatformCredential::Linux(map) = map {
+        let ss = SecretService::new(EncryptionType::Dh).map_err(decode_error)?;
+        let collection = get_collection(map, &ss)?;
+        let search = collection
+            .search_items(map.attributes())
+            .map_err(decode_error)?;
+        let item = search.first().ok_or(ErrorCode::NoEntry)?;
+        map.attributes = item.get_attributes().map_err(decode_error)?;
+        decode_attributes(map, item);
+        Ok(())
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+// Passwords have no platform limit on their size.
+pub fn password_capacity() -> Option<usize> {
+    None
+}
+
+pub fn password_len(password: &str) -> usize {
+    password.len()
+}
+
 pub fn get_password(map: &mut PlatformCredential) -> Result<String> {
+    // Linux keyring allows non-UTF8 values, but this library only supports adding UTF8 items
+    // to the keyring, so this should only fail if we are trying to retrieve a non-UTF8
+    // password that was added to the keyring by another library
+    decode_password(get_secret(map)?)
+}
+
+// Read the secret of a credential exactly as it's stored.
+pub fn get_secret(map: &mut PlatformCredential) -> Result<Vec<u8>> {
     if let PlatformCredential::Linux(map) = map {
         let ss = SecretService::new(EncryptionType::Dh).map_err(decode_error)?;
         let collection = get_collection(map, &ss)?;
         let search = collection
             .search_items(map.attributes())
             .map_err(decode_error)?;
-        let item = search.get(0).ok_or(ErrorCode::NoEntry)?;
+        let item = search.first().ok_or(ErrorCode::NoEntry)?;
         let bytes = item.get_secret().map_err(decode_error)?;
-        // Linux keyring allows non-UTF8 values, but this library only supports adding UTF8 items
-        // to the keyring, so this should only fail if we are trying to retrieve a non-UTF8
-        // password that was added to the keyring by another library
         decode_attributes(map, item);
-        decode_password(bytes)
+        Ok(bytes)
     } else {
         Err(ErrorCode::WrongCredentialPlatform)
     }
@@ -64,7 +173,7 @@ pub fn delete_password(map: &PlatformCredential) -> Result<()> {
         let search = collection
             .search_items(map.attributes())
             .map_err(decode_error)?;
-        let item = search.get(0).ok_or(ErrorCode::NoEntry)?;
+        let item = search.first().ok_or(ErrorCode::NoEntry)?;
         item.delete().map_err(decode_error)?;
         Ok(())
     } else {
@@ -72,8 +181,136 @@ pub fn delete_password(map: &PlatformCredential) -> Result<()> {
     }
 }
 
+// Read the password and the metadata of a credential from the same item,
+// so they are always consistent with each other.
+pub fn get_password_and_metadata(
+    map: &mut PlatformCredential,
+) -> Result<(String, CredentialMetadata)> {
+    if let PlatformCredential::Linux(map) = map {
+        let ss = SecretService::new(EncryptionType::Dh).map_err(decode_error)?;
+        let collection = get_collection(map, &ss)?;
+        let search = collection
+            .search_items(map.attributes())
+            .map_err(decode_error)?;
+        let item = search.first().ok_or(ErrorCode::NoEntry)?;
+        let bytes = item.get_secret().map_err(decode_error)?;
+        decode_attributes(map, item);
+        Ok((decode_password(bytes)?, decode_metadata(item)?))
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+pub fn get_metadata(map: &PlatformCredential) -> Result<CredentialMetadata> {
+    if let PlatformCredential::Linux(map) = map {
+        let ss = SecretService::new(EncryptionType::Dh).map_err(decode_error)?;
+        let collection = get_collection(map, &ss)?;
+        let search = collection
+            .search_items(map.attributes())
+            .map_err(decode_error)?;
+        let item = search.first().ok_or(ErrorCode::NoEntry)?;
+        decode_metadata(item)
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+// The lock state is read before (and instead of) unlocking the collection.
+pub fn get_access_control(map: &PlatformCredential) -> Result<AccessControl> {
+    if let PlatformCredential::Linux(map) = map {
+        let ss = SecretService::new(EncryptionType::Dh).map_err(decode_error)?;
+        let collection = find_collection(map, &ss)?;
+        let locked = collection.is_locked().map_err(decode_error)?;
+        let search = collection
+            .search_items(map.attributes())
+            .map_err(decode_error)?;
+        search.first().ok_or(ErrorCode::NoEntry)?;
+        Ok(AccessControl {
+            locked: Some(locked),
+            ..AccessControl::default()
+        })
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+// List the credentials in the default collection.  Locked items can
+// still be listed, because their attributes are not secret.
+pub fn list_credentials() -> Result<Vec<PlatformCredential>> {
+    let ss = SecretService::new(EncryptionType::Dh).map_err(decode_error)?;
+    let collection = ss.get_default_collection().map_err(decode_error)?;
+    let mut result = Vec::new();
+    for item in collection.get_all_items().map_err(decode_error)? {
+        let credential = PlatformCredential::Linux(LinuxCredential {
+            collection: "default".to_string(),
+            attributes: item.get_attributes().map_err(decode_error)?,
+            label: item.get_label().map_err(decode_error)?,
+        });
+        if !credential.is_companion() {
+            result.push(credential);
+        }
+    }
+    Ok(result)
+}
+
+// List the items in the default collection, with their metadata.  The
+// secret service has no way to read all of an item's properties at once,
+// so this reads two more properties of each item than `list_credentials`,
+// but still doesn't search for each item again.
+pub fn list_credentials_with_metadata() -> Result<Vec<(PlatformCredential, CredentialMetadata)>> {
+    let ss = SecretService::new(EncryptionType::Dh).map_err(decode_error)?;
+    let collection = ss.get_default_collection().map_err(decode_error)?;
+    let mut result = Vec::new();
+    for item in collection.get_all_items().map_err(decode_error)? {
+        let credential = PlatformCredential::Linux(LinuxCredential {
+            collection: "default".to_string(),
+            attributes: item.get_attributes().map_err(decode_error)?,
+            label: item.get_label().map_err(decode_error)?,
+        });
+        if !credential.is_companion() {
+            result.push((credential, decode_metadata(&item)?));
+        }
+    }
+    Ok(result)
+}
+
+// Which Secret Service this process talks to: the address of the D-Bus
+// session bus it connects to, the Unix user id of the process owning
+// the Secret Service on that bus, and the user id the bus sees this
+// process as.  If the two user ids differ, this process is using some
+// other user's keyring (or a root-owned one).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionIdentity {
+    pub bus_address: String,
+    pub service_uid: u32,
+    pub client_uid: u32,
+}
+
+// Find out which Secret Service this process talks to.  This only asks
+// the bus about the service; it doesn't start the service if it isn't
+// running (that fails with a `PlatformFailure`), nor touch any keyring.
+pub fn session_identity() -> Result<SessionIdentity> {
+    let connection = zbus::Connection::new_session().map_err(|e| decode_error(Error::Zbus(e)))?;
+    let bus = zbus::fdo::DBusProxy::new(&connection).map_err(|e| decode_error(Error::Zbus(e)))?;
+    let uid = |name: &str| {
+        bus.get_connection_unix_user(name)
+            .map_err(|e| decode_error(Error::ZbusFdo(e)))
+    };
+    let client_uid = uid(connection.unique_name().unwrap_or_default())?;
+    let service_uid = uid("org.freedesktop.secrets")?;
+    // without an address in the environment, the bus is found in the
+    // user's runtime directory
+    let bus_address = std::env::var("DBUS_SESSION_BUS_ADDRESS")
+        .unwrap_or_else(|_| format!("unix:path=/run/user/{}/bus", client_uid));
+    Ok(SessionIdentity {
+        bus_address,
+        service_uid,
+        client_uid,
+    })
+}
+
 fn decode_password(bytes: Vec<u8>) -> Result<String> {
-    String::from_utf8(bytes.clone()).map_err(|_| ErrorCode::BadEncoding(bytes))
+    String::from_utf8(bytes).map_err(|err| ErrorCode::BadEncoding(err.into_bytes()))
 }
 
 fn decode_error(err: Error) -> ErrorCode {
@@ -86,7 +323,25 @@ fn decode_error(err: Error) -> ErrorCode {
         Error::Locked => ErrorCode::NoStorageAccess(err),
         Error::NoResult => ErrorCode::NoStorageAccess(err),
         Error::Parse => ErrorCode::PlatformFailure(err),
-        Error::Prompt => ErrorCode::NoStorageAccess(err),
+        // the only prompt failure is the user dismissing the prompt
+        Error::Prompt => ErrorCode::UserCanceled(err),
+    }
+}
+
+fn decode_metadata(item: &Item) -> Result<CredentialMetadata> {
+    Ok(CredentialMetadata {
+        created: decode_time(item.get_created().map_err(decode_error)?),
+        modified: decode_time(item.get_modified().map_err(decode_error)?),
+        persistence: None,
+        expires: None,
+    })
+}
+
+// Secret service times are seconds since the epoch, with zero meaning unknown.
+fn decode_time(secs: u64) -> Option<SystemTime> {
+    match secs {
+        0 => None,
+        secs => Some(UNIX_EPOCH + Duration::from_secs(secs)),
     }
 }
 
@@ -100,6 +355,44 @@ fn decode_attributes(map: &mut LinuxCredential, item: &Item) {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_error_messages() {
+        let unknown = zbus::fdo::Error::ServiceUnknown("Not activatable".to_string());
+        assert_eq!(
+            error_message(&Error::ZbusFdo(unknown)).as_deref(),
+            Some("Not activatable")
+        );
+        assert!(error_message(&Error::Locked).is_none());
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        assert!(ErrorCode::NoStorageAccess(Error::Locked).is_transient());
+        assert!(!ErrorCode::PlatformFailure(Error::NoResult).is_transient());
+        assert!(!ErrorCode::UserCanceled(Error::Prompt).is_transient());
+    }
+
+    #[test]
+    fn test_dismissed_prompt() {
+        assert!(matches!(
+            decode_error(Error::Prompt),
+            ErrorCode::UserCanceled(Error::Prompt)
+        ));
+        assert!(matches!(
+            decode_error(Error::Locked),
+            ErrorCode::NoStorageAccess(Error::Locked)
+        ));
+    }
+
+    #[test]
+    fn test_session_identity() {
+        // make sure the service is running
+        list_credentials().unwrap();
+        let identity = session_identity().unwrap();
+        assert_eq!(identity.service_uid, identity.client_uid);
+        assert!(!identity.bus_address.is_empty());
+    }
+
     #[test]
     fn test_bad_password() {
         // malformed sequences here taken from:
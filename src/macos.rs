@@ -1,7 +1,36 @@
+use core_foundation::array::{CFArray, CFArrayRef};
+use core_foundation::base::{CFGetTypeID, CFType, CFTypeRef, OSStatus, TCFType};
+use core_foundation::boolean::CFBoolean;
+use core_foundation::data::{CFData, CFDataRef};
+use core_foundation::date::CFDate;
+use core_foundation::dictionary::CFDictionary;
+use core_foundation::number::CFNumber;
+use core_foundation::string::{CFString, CFStringRef};
+use security_framework::item::{ItemClass, ItemSearchOptions, Limit, Reference, SearchResult};
+use security_framework::os::macos::item::ItemSearchOptionsExt;
 use security_framework::os::macos::keychain::{SecKeychain, SecPreferencesDomain};
 use security_framework::os::macos::passwords::find_generic_password;
+use security_framework_sys::access_control::{
+    kSecAttrAccessibleAfterFirstUnlock, kSecAttrAccessibleAfterFirstUnlockThisDeviceOnly,
+    kSecAttrAccessibleAlways, kSecAttrAccessibleAlwaysThisDeviceOnly,
+    kSecAttrAccessibleWhenPasscodeSetThisDeviceOnly, kSecAttrAccessibleWhenUnlocked,
+    kSecAttrAccessibleWhenUnlockedThisDeviceOnly,
+};
+use security_framework_sys::base::{SecAccessRef, SecKeychainItemRef, SecKeychainRef};
+use security_framework_sys::item::{
+    kSecAttrAccount, kSecAttrComment, kSecAttrService, kSecAttrSynchronizable, kSecClass,
+    kSecClassGenericPassword, kSecMatchSearchList,
+};
+use security_framework_sys::keychain_item::SecItemUpdate;
+use std::os::raw::{c_char, c_void};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use crate::credential::{MacCredential, MacKeychainDomain};
+use crate::credential::{
+    AccessControl, CredentialMetadata, MacAccessibility, MacCredential, MacKeychainDomain,
+};
 use crate::{Error as ErrorCode, Platform, PlatformCredential, Result};
 
 pub fn platform() -> Platform {
@@ -10,8 +39,57 @@ pub fn platform() -> Platform {
 
 pub use security_framework::base::Error;
 
+// These item attribute keys aren't exported by security-framework-sys.
+#[link(name = "Security", kind = "framework")]
+extern "C" {
+    static kSecAttrCreator: CFStringRef;
+    static kSecAttrType: CFStringRef;
+    static kSecAttrCreationDate: CFStringRef;
+    static kSecAttrModificationDate: CFStringRef;
+    static kSecAttrAccessible: CFStringRef;
+}
+
+// The (deprecated, but still only) way to read a keychain item's
+// access control lists.  ACL and trusted application references are
+// CoreFoundation types.
+#[link(name = "Security", kind = "framework")]
+extern "C" {
+    fn SecKeychainItemCopyAccess(item: SecKeychainItemRef, access: *mut SecAccessRef) -> OSStatus;
+    fn SecAccessCopyACLList(access: SecAccessRef, acl_list: *mut CFArrayRef) -> OSStatus;
+    fn SecACLCopyContents(
+        acl: CFTypeRef,
+        application_list: *mut CFArrayRef,
+        description: *mut CFStringRef,
+        prompt_selector: *mut u16,
+    ) -> OSStatus;
+    fn SecTrustedApplicationCopyData(application: CFTypeRef, data: *mut CFDataRef) -> OSStatus;
+}
+
+// The (also deprecated) keychain search list, the path of each keychain
+// in it, and a keychain's lock and permission status, aren't wrapped by
+// security-framework.
+#[link(name = "Security", kind = "framework")]
+extern "C" {
+    fn SecKeychainGetStatus(keychain: SecKeychainRef, status: *mut u32) -> OSStatus;
+    fn SecKeychainCopySearchList(search_list: *mut CFArrayRef) -> OSStatus;
+    fn SecKeychainGetPath(
+        keychain: SecKeychainRef,
+        length: *mut u32,
+        path_name: *mut c_char,
+    ) -> OSStatus;
+}
+
 fn get_keychain(map: &MacCredential) -> Result<SecKeychain> {
-    let domain = match map.domain {
+    get_domain_keychain(&map.domain)
+}
+
+fn get_domain_keychain(domain: &MacKeychainDomain) -> Result<SecKeychain> {
+    if let MacKeychainDomain::User = domain {
+        if let Some((_, keychain)) = scoped_keychains().last() {
+            return Ok(keychain.clone());
+        }
+    }
+    let domain = match domain {
         MacKeychainDomain::User => SecPreferencesDomain::User,
         MacKeychainDomain::System => SecPreferencesDomain::System,
         MacKeychainDomain::Common => SecPreferencesDomain::Common,
@@ -23,28 +101,285 @@ fn get_keychain(map: &MacCredential) -> Result<SecKeychain> {
     }
 }
 
+// While one of these guards is alive, the given keychain stands in for
+// the User domain's default keychain in all of this crate's operations.
+// The keychain the system considers the default is never changed (that's
+// a preference of the user's, shared by every process and kept across
+// runs, so a crash would leave it changed): only this crate's choice of
+// keychain is.  Dropping the guard, including during a panic, restores
+// the crate's previous choice.  Guards can be nested,
+// in which case the most recently created live guard's keychain is used.
+//
+// This is meant for making tests and tools hermetic.  Note that the
+// substitution is process-wide, so it also applies to other threads.
+pub struct ScopedCrateKeychain {
+    id: u64,
+}
+
+static SCOPED_KEYCHAINS: Mutex<Vec<(u64, SecKeychain)>> = Mutex::new(Vec::new());
+static NEXT_SCOPE_ID: AtomicU64 = AtomicU64::new(0);
+
+impl ScopedCrateKeychain {
+    // Use the keychain file at the given path as the default.  This fails
+    // with `NoStorageAccess` if there is no keychain file there.
+    pub fn new(path: &Path) -> Result<ScopedCrateKeychain> {
+        if !path.is_file() {
+            return Err(decode_error(Error::from_code(-25294))); // errSecNoSuchKeychain
+        }
+        let keychain = SecKeychain::open(path).map_err(decode_error)?;
+        let id = NEXT_SCOPE_ID.fetch_add(1, Ordering::Relaxed);
+        scoped_keychains().push((id, keychain));
+        Ok(ScopedCrateKeychain { id })
+    }
+}
+
+impl Drop for ScopedCrateKeychain {
+    fn drop(&mut self) {
+        scoped_keychains().retain(|(id, _)| *id != self.id);
+    }
+}
+
+fn scoped_keychains() -> MutexGuard<'static, Vec<(u64, SecKeychain)>> {
+    SCOPED_KEYCHAINS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// Run an operation with user interaction turned off, so that anything
+// that would prompt the user fails with `NoInteraction` instead.  This is
+// process-wide while the operation runs.
+pub fn without_interaction<T>(operation: impl FnOnce() -> Result<T>) -> Result<T> {
+    let _lock = SecKeychain::disable_user_interaction().map_err(decode_error)?;
+    operation()
+}
+
+// Whether an error is one that may go away if the operation is retried.
+// There are no such errors on this platform.
+pub fn is_transient(_err: &ErrorCode) -> bool {
+    false
+}
+
+// The keychain's message for an error code (from `SecCopyErrorMessageString`).
+pub fn error_message(err: &Error) -> Option<String> {
+    err.message()
+}
+
+// Keychain domains always exist, so there is nothing to create.
+pub fn ensure_store(_map: &PlatformCredential) -> Result<()> {
+    Ok(())
+}
+
 pub fn set_password(map: &PlatformCredential, password: &str) -> Result<()> {
+    set_secret(map, password.as_bytes())
+}
+
+// Store the secret of a credential exactly as given.
+pub fn set_secret(map: &PlatformCredential, secret: &[u8]) -> Result<()> {
     if let PlatformCredential::Mac(map) = map {
-        get_keychain(map)?
-            .set_generic_password(&map.service, &map.account, password.as_bytes())
+        let keychain = get_keychain(map)?;
+        keychain
+            .set_generic_password(&map.service, &map.account, secret)
             .map_err(decode_error)?;
-        Ok(())
+        set_attributes(map, &keychain)
     } else {
         Err(ErrorCode::WrongCredentialPlatform)
     }
 }
 
+// Passwords have no platform limit on their size.
+pub fn password_capacity() -> Option<usize> {
+    None
+}
+
+pub fn password_len(password: &str) -> usize {
+    password.len()
+}
+
 pub fn get_password(map: &mut PlatformCredential) -> Result<String> {
+    Ok(get_password_and_metadata(map)?.0)
+}
+
+// Read the secret of a credential exactly as it's stored.
+pub fn get_secret(map: &mut PlatformCredential) -> Result<Vec<u8>> {
     if let PlatformCredential::Mac(map) = map {
-        let (password_bytes, _) =
-            find_generic_password(Some(&[get_keychain(map)?]), &map.service, &map.account)
-                .map_err(decode_error)?;
-        decode_password(password_bytes.to_vec())
+        let keychain = get_keychain(map)?;
+        let (password_bytes, _) = find_generic_password(
+            Some(std::slice::from_ref(&keychain)),
+            &map.service,
+            &map.account,
+        )
+        .map_err(decode_error)?;
+        if let Some(attributes) = find_attributes(map, &keychain)? {
+            decode_attributes(map, &attributes);
+        }
+        Ok(password_bytes.to_vec())
     } else {
         Err(ErrorCode::WrongCredentialPlatform)
     }
 }
 
+// Read the password and the metadata of a credential.  The password item
+// doesn't carry the attributes, so they are read separately.
+pub fn get_password_and_metadata(
+    map: &mut PlatformCredential,
+) -> Result<(String, CredentialMetadata)> {
+    if let PlatformCredential::Mac(map) = map {
+        let keychain = get_keychain(map)?;
+        let (password_bytes, _) = find_generic_password(
+            Some(std::slice::from_ref(&keychain)),
+            &map.service,
+            &map.account,
+        )
+        .map_err(decode_error)?;
+        let password = decode_password(password_bytes.to_vec())?;
+        let metadata = match find_attributes(map, &keychain)? {
+            Some(attributes) => {
+                decode_attributes(map, &attributes);
+                decode_metadata(&attributes)
+            }
+            None => CredentialMetadata::default(),
+        };
+        Ok((password, metadata))
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+// Whether the credential's keychain is unlocked and writable.  This
+// doesn't unlock it.
+pub fn is_writable(map: &PlatformCredential) -> Result<bool> {
+    if let PlatformCredential::Mac(map) = map {
+        let keychain = get_keychain(map)?;
+        let mut status = 0;
+        check(unsafe { SecKeychainGetStatus(keychain.as_concrete_TypeRef(), &mut status) })?;
+        // kSecUnlockStateStatus and kSecWritePermStatus
+        Ok(status & 1 != 0 && status & 4 != 0)
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+// Read the codes and comment of a stored item into the credential,
+// without reading its password data.
+pub fn get_attributes(map: &mut PlatformCredential) -> Result<()> {
+    if let PlatformCredential::Mac(map) = map {
+        let attributes = find_attributes(map, &get_keychain(map)?)?.ok_or(ErrorCode::NoEntry)?;
+        decode_attributes(map, &attributes);
+        Ok(())
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+pub fn get_metadata(map: &PlatformCredential) -> Result<CredentialMetadata> {
+    if let PlatformCredential::Mac(map) = map {
+        let attributes = find_attributes(map, &get_keychain(map)?)?.ok_or(ErrorCode::NoEntry)?;
+        Ok(decode_metadata(&attributes))
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+pub fn get_access_control(map: &PlatformCredential) -> Result<AccessControl> {
+    if let PlatformCredential::Mac(map) = map {
+        let keychain = get_keychain(map)?;
+        let attributes = find_attributes(map, &keychain)?.ok_or(ErrorCode::NoEntry)?;
+        Ok(AccessControl {
+            accessibility: decode_accessibility(&attributes),
+            synchronizable: Some(decode_bool(&attributes, unsafe { kSecAttrSynchronizable })),
+            trusted_applications: Some(find_trusted_applications(map, &keychain)?),
+            ..AccessControl::default()
+        })
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+// Read the paths of the applications that any of an item's access
+// control lists trusts, without reading the item's password.  An ACL
+// that trusts every application has no list, so it adds nothing.
+fn find_trusted_applications(map: &MacCredential, keychain: &SecKeychain) -> Result<Vec<String>> {
+    let search = ItemSearchOptions::new()
+        .class(ItemClass::generic_password())
+        .keychains(std::slice::from_ref(keychain))
+        .service(&map.service)
+        .account(&map.account)
+        .load_refs(true)
+        .limit(1)
+        .search()
+        .map_err(decode_error)?;
+    let item = search
+        .into_iter()
+        .find_map(|result| match result {
+            SearchResult::Ref(Reference::KeychainItem(item)) => Some(item),
+            _ => None,
+        })
+        .ok_or(ErrorCode::NoEntry)?;
+    let mut applications = Vec::new();
+    unsafe {
+        let mut access = std::ptr::null_mut();
+        check(SecKeychainItemCopyAccess(
+            item.as_concrete_TypeRef(),
+            &mut access,
+        ))?;
+        let access = CFType::wrap_under_create_rule(access as CFTypeRef);
+        let mut acl_list = std::ptr::null();
+        check(SecAccessCopyACLList(
+            access.as_CFTypeRef() as SecAccessRef,
+            &mut acl_list,
+        ))?;
+        let acl_list: CFArray<CFType> = CFArray::wrap_under_create_rule(acl_list);
+        for acl in acl_list.iter() {
+            let (mut list, mut description, mut prompt_selector) =
+                (std::ptr::null(), std::ptr::null(), 0);
+            check(SecACLCopyContents(
+                acl.as_CFTypeRef(),
+                &mut list,
+                &mut description,
+                &mut prompt_selector,
+            ))?;
+            if !description.is_null() {
+                CFString::wrap_under_create_rule(description);
+            }
+            if list.is_null() {
+                continue;
+            }
+            let list: CFArray<CFType> = CFArray::wrap_under_create_rule(list);
+            for application in list.iter() {
+                let mut data = std::ptr::null();
+                check(SecTrustedApplicationCopyData(
+                    application.as_CFTypeRef(),
+                    &mut data,
+                ))?;
+                let data = CFData::wrap_under_create_rule(data);
+                // the data is the application's path, as a C string
+                let path = String::from_utf8_lossy(data.bytes())
+                    .trim_end_matches('\0')
+                    .to_string();
+                if !applications.contains(&path) {
+                    applications.push(path);
+                }
+            }
+        }
+    }
+    Ok(applications)
+}
+
+// Read the attributes of a credential's item (but not its password).
+fn find_attributes(map: &MacCredential, keychain: &SecKeychain) -> Result<Option<CFDictionary>> {
+    let search = ItemSearchOptions::new()
+        .class(ItemClass::generic_password())
+        .keychains(std::slice::from_ref(keychain))
+        .service(&map.service)
+        .account(&map.account)
+        .load_attributes(true)
+        .limit(1)
+        .search()
+        .map_err(decode_error)?;
+    Ok(search.into_iter().find_map(|result| match result {
+        SearchResult::Dict(attributes) => Some(attributes),
+        _ => None,
+    }))
+}
+
 pub fn delete_password(map: &PlatformCredential) -> Result<()> {
     if let PlatformCredential::Mac(map) = map {
         let (_, item) =
@@ -57,20 +392,352 @@ pub fn delete_password(map: &PlatformCredential) -> Result<()> {
     }
 }
 
+// List the generic passwords in the User keychain.  This reads only
+// the attributes of each item, never its password data.
+pub fn list_credentials() -> Result<Vec<PlatformCredential>> {
+    Ok(list_credentials_with_metadata()?
+        .into_iter()
+        .map(|(credential, _)| credential)
+        .collect())
+}
+
+// List the generic passwords in the User keychain, with their metadata,
+// which is among the attributes read when listing.
+pub fn list_credentials_with_metadata() -> Result<Vec<(PlatformCredential, CredentialMetadata)>> {
+    let keychain = get_domain_keychain(&MacKeychainDomain::User)?;
+    Ok(list_keychain(keychain)?
+        .into_iter()
+        .map(|(credential, metadata)| (PlatformCredential::Mac(credential), metadata))
+        .collect())
+}
+
+// Unlock the keychain file at the given path with its password, and list
+// the generic passwords in it.  Like `list_credentials`, this never reads
+// any password data.  The listed credentials are in the User domain, so
+// their passwords can be read (each of which may prompt the user) while a
+// `ScopedCrateKeychain` guard for the same file is alive.
+pub fn open_keychain_file(path: &Path, password: &str) -> Result<Vec<MacCredential>> {
+    if !path.is_file() {
+        return Err(decode_error(Error::from_code(-25294))); // errSecNoSuchKeychain
+    }
+    let mut keychain = SecKeychain::open(path).map_err(decode_error)?;
+    keychain.unlock(Some(password)).map_err(decode_error)?;
+    Ok(list_keychain(keychain)?
+        .into_iter()
+        .map(|(credential, _)| credential)
+        .collect())
+}
+
+// Identifies a keychain in the search list by the path of its file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeychainId(pub PathBuf);
+
+// The keychains in the user's search list, in search order.
+fn search_list() -> Result<Vec<(KeychainId, SecKeychain)>> {
+    let mut keychains = Vec::new();
+    unsafe {
+        let mut list = std::ptr::null();
+        check(SecKeychainCopySearchList(&mut list))?;
+        let list: CFArray<CFType> = CFArray::wrap_under_create_rule(list);
+        for keychain in list.iter() {
+            let keychain = SecKeychain::wrap_under_get_rule(keychain.as_CFTypeRef() as _);
+            let mut path = [0 as c_char; 1024];
+            let mut length = path.len() as u32;
+            check(SecKeychainGetPath(
+                keychain.as_concrete_TypeRef(),
+                &mut length,
+                path.as_mut_ptr(),
+            ))?;
+            let path = std::slice::from_raw_parts(path.as_ptr() as *const u8, length as usize);
+            let path = PathBuf::from(String::from_utf8_lossy(path).into_owned());
+            keychains.push((KeychainId(path), keychain));
+        }
+    }
+    Ok(keychains)
+}
+
+// List every keychain in the search list that holds a generic password
+// for the service and account, in search order, with the item each one
+// holds (read without its password data).  Reads through the service
+// and account only ever see the first of these, so when there are more
+// than one, which password is read depends on the order of the search
+// list; see `consolidate`.
+pub fn find_duplicates_across_keychains(
+    service: &str,
+    account: &str,
+) -> Result<Vec<(KeychainId, MacCredential)>> {
+    let mut found = Vec::new();
+    for (id, keychain) in search_list()? {
+        let mut map = MacCredential {
+            domain: MacKeychainDomain::User,
+            service: service.to_string(),
+            account: account.to_string(),
+            creator_code: None,
+            type_code: None,
+            comment: None,
+        };
+        let attributes = match find_attributes(&map, &keychain) {
+            Ok(attributes) => attributes,
+            Err(ErrorCode::NoEntry) => None,
+            Err(err) => return Err(err),
+        };
+        if let Some(attributes) = attributes {
+            decode_attributes(&mut map, &attributes);
+            found.push((id, map));
+        }
+    }
+    Ok(found)
+}
+
+// Keep the generic password for the service and account that's in the
+// given keychain, and delete the ones in every other keychain in the
+// search list, returning how many were deleted.  This fails with
+// `NoEntry` (and deletes nothing) if the keychain to keep doesn't hold
+// one.
+pub fn consolidate(service: &str, account: &str, keep: &KeychainId) -> Result<usize> {
+    let keychains = search_list()?;
+    let holders: Vec<KeychainId> = find_duplicates_across_keychains(service, account)?
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    if !holders.contains(keep) {
+        return Err(ErrorCode::NoEntry);
+    }
+    let mut deleted = 0;
+    for (id, keychain) in keychains {
+        if id == *keep || !holders.contains(&id) {
+            continue;
+        }
+        let (_, item) =
+            find_generic_password(Some(&[keychain]), service, account).map_err(decode_error)?;
+        item.delete();
+        deleted += 1;
+    }
+    Ok(deleted)
+}
+
+fn list_keychain(keychain: SecKeychain) -> Result<Vec<(MacCredential, CredentialMetadata)>> {
+    let search = ItemSearchOptions::new()
+        .class(ItemClass::generic_password())
+        .keychains(&[keychain])
+        .load_attributes(true)
+        .limit(Limit::All)
+        .search();
+    let results = match search {
+        Ok(results) => results,
+        Err(err) if err.code() == -25300 => return Ok(Vec::new()), // errSecItemNotFound
+        Err(err) => return Err(decode_error(err)),
+    };
+    let mut credentials = Vec::new();
+    for result in results.iter() {
+        if let (Some(strings), SearchResult::Dict(attributes)) = (result.simplify_dict(), result) {
+            let mut map = MacCredential {
+                domain: MacKeychainDomain::User,
+                service: strings.get("svce").cloned().unwrap_or_default(),
+                account: strings.get("acct").cloned().unwrap_or_default(),
+                creator_code: None,
+                type_code: None,
+                comment: None,
+            };
+            decode_attributes(&mut map, attributes);
+            if !PlatformCredential::Mac(map.clone()).is_companion() {
+                credentials.push((map, decode_metadata(attributes)));
+            }
+        }
+    }
+    Ok(credentials)
+}
+
+// Set the creator and type codes and the comment of an existing item,
+// if the credential specifies them.
+fn set_attributes(map: &MacCredential, keychain: &SecKeychain) -> Result<()> {
+    let mut attributes: Vec<(CFString, CFType)> = Vec::new();
+    let codes = [
+        (unsafe { kSecAttrCreator }, map.creator_code),
+        (unsafe { kSecAttrType }, map.type_code),
+    ];
+    for (key, code) in codes {
+        if let Some(code) = code {
+            attributes.push((
+                unsafe { CFString::wrap_under_get_rule(key) },
+                CFNumber::from(i32::from_be_bytes(code)).into_CFType(),
+            ));
+        }
+    }
+    if let Some(comment) = &map.comment {
+        attributes.push((
+            unsafe { CFString::wrap_under_get_rule(kSecAttrComment) },
+            CFString::new(comment).into_CFType(),
+        ));
+    }
+    if attributes.is_empty() {
+        return Ok(());
+    }
+    let attributes = CFDictionary::from_CFType_pairs(&attributes);
+    let query = unsafe {
+        CFDictionary::from_CFType_pairs(&[
+            (
+                CFString::wrap_under_get_rule(kSecClass),
+                CFString::wrap_under_get_rule(kSecClassGenericPassword).into_CFType(),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecAttrService),
+                CFString::new(&map.service).into_CFType(),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecAttrAccount),
+                CFString::new(&map.account).into_CFType(),
+            ),
+            (
+                CFString::wrap_under_get_rule(kSecMatchSearchList),
+                CFArray::from_CFTypes(std::slice::from_ref(keychain)).into_CFType(),
+            ),
+        ])
+    };
+    match unsafe {
+        SecItemUpdate(
+            query.as_concrete_TypeRef(),
+            attributes.as_concrete_TypeRef(),
+        )
+    } {
+        0 => Ok(()),
+        status => Err(decode_error(Error::from_code(status))),
+    }
+}
+
+fn decode_attributes(map: &mut MacCredential, attributes: &CFDictionary) {
+    map.creator_code = decode_code(attributes, unsafe { kSecAttrCreator });
+    map.type_code = decode_code(attributes, unsafe { kSecAttrType });
+    map.comment = decode_string(attributes, unsafe { kSecAttrComment });
+}
+
+fn decode_metadata(attributes: &CFDictionary) -> CredentialMetadata {
+    CredentialMetadata {
+        created: decode_date(attributes, unsafe { kSecAttrCreationDate }),
+        modified: decode_date(attributes, unsafe { kSecAttrModificationDate }),
+        persistence: None,
+        expires: decode_string(attributes, unsafe { kSecAttrComment })
+            .as_deref()
+            .and_then(crate::credential::parse_expiry),
+    }
+}
+
+fn decode_string(attributes: &CFDictionary, key: CFStringRef) -> Option<String> {
+    let value = attributes.find(key as *const c_void)?;
+    if unsafe { CFGetTypeID(*value) } != CFString::type_id() {
+        return None;
+    }
+    Some(unsafe { CFString::wrap_under_get_rule(*value as _) }.to_string())
+}
+
+// Keychain dates are seconds since January 1, 2001 (UTC).
+fn decode_date(attributes: &CFDictionary, key: CFStringRef) -> Option<SystemTime> {
+    const EPOCH_DIFFERENCE_SECS: f64 = 978_307_200.0;
+    let value = attributes.find(key as *const c_void)?;
+    if unsafe { CFGetTypeID(*value) } != CFDate::type_id() {
+        return None;
+    }
+    let date = unsafe { CFDate::wrap_under_get_rule(*value as _) };
+    let since_1970 = date.abs_time() + EPOCH_DIFFERENCE_SECS;
+    if since_1970 < 0.0 {
+        return None;
+    }
+    Some(UNIX_EPOCH + Duration::from_secs_f64(since_1970))
+}
+
+fn decode_accessibility(attributes: &CFDictionary) -> Option<MacAccessibility> {
+    let value = attributes.find(unsafe { kSecAttrAccessible } as *const c_void)?;
+    if unsafe { CFGetTypeID(*value) } != CFString::type_id() {
+        return None;
+    }
+    let value = unsafe { CFString::wrap_under_get_rule(*value as _) };
+    let levels = unsafe {
+        [
+            (
+                kSecAttrAccessibleWhenUnlocked,
+                MacAccessibility::WhenUnlocked,
+            ),
+            (
+                kSecAttrAccessibleAfterFirstUnlock,
+                MacAccessibility::AfterFirstUnlock,
+            ),
+            (kSecAttrAccessibleAlways, MacAccessibility::Always),
+            (
+                kSecAttrAccessibleWhenPasscodeSetThisDeviceOnly,
+                MacAccessibility::WhenPasscodeSetThisDeviceOnly,
+            ),
+            (
+                kSecAttrAccessibleWhenUnlockedThisDeviceOnly,
+                MacAccessibility::WhenUnlockedThisDeviceOnly,
+            ),
+            (
+                kSecAttrAccessibleAfterFirstUnlockThisDeviceOnly,
+                MacAccessibility::AfterFirstUnlockThisDeviceOnly,
+            ),
+            (
+                kSecAttrAccessibleAlwaysThisDeviceOnly,
+                MacAccessibility::AlwaysThisDeviceOnly,
+            ),
+        ]
+    };
+    levels.iter().find_map(|(key, level)| {
+        (value == unsafe { CFString::wrap_under_get_rule(*key) }).then_some(*level)
+    })
+}
+
+// Boolean attributes may be stored as booleans or numbers; absent is false.
+fn decode_bool(attributes: &CFDictionary, key: CFStringRef) -> bool {
+    let value = match attributes.find(key as *const c_void) {
+        Some(value) => *value,
+        None => return false,
+    };
+    if unsafe { CFGetTypeID(value) } == CFBoolean::type_id() {
+        bool::from(unsafe { CFBoolean::wrap_under_get_rule(value as _) })
+    } else if unsafe { CFGetTypeID(value) } == CFNumber::type_id() {
+        let number = unsafe { CFNumber::wrap_under_get_rule(value as _) };
+        number.to_i64().unwrap_or(0) != 0
+    } else {
+        false
+    }
+}
+
+fn check(status: OSStatus) -> Result<()> {
+    match status {
+        0 => Ok(()),
+        status => Err(decode_error(Error::from_code(status))),
+    }
+}
+
+// Four-character codes are stored as numbers; an unset code is absent or zero.
+fn decode_code(attributes: &CFDictionary, key: CFStringRef) -> Option<[u8; 4]> {
+    let value = attributes.find(key as *const c_void)?;
+    if unsafe { CFGetTypeID(*value) } != CFNumber::type_id() {
+        return None;
+    }
+    let number = unsafe { CFNumber::wrap_under_get_rule(*value as _) };
+    match number.to_i64()? as u32 {
+        0 => None,
+        code => Some(code.to_be_bytes()),
+    }
+}
+
 fn decode_password(bytes: Vec<u8>) -> Result<String> {
     // Mac keychain allows non-UTF8 values, passwords from 3rd parties may not be UTF-8.
-    String::from_utf8(bytes.clone()).map_err(|_| ErrorCode::BadEncoding(bytes))
+    String::from_utf8(bytes).map_err(|err| ErrorCode::BadEncoding(err.into_bytes()))
 }
 
 // The MacOS error codes used here are from:
 // https://opensource.apple.com/source/libsecurity_keychain/libsecurity_keychain-78/lib/SecBase.h.auto.html
 fn decode_error(err: Error) -> ErrorCode {
     match err.code() {
+        -128 => ErrorCode::UserCanceled(err),      // errSecUserCanceled
         -25291 => ErrorCode::NoStorageAccess(err), // errSecNotAvailable
-        -25292 => ErrorCode::NoStorageAccess(err), // errSecReadOnly
+        -61 => ErrorCode::ReadOnly(err),           // errSecWrPerm
+        -25292 => ErrorCode::ReadOnly(err),        // errSecReadOnly
         -25294 => ErrorCode::NoStorageAccess(err), // errSecNoSuchKeychain
         -25295 => ErrorCode::NoStorageAccess(err), // errSecInvalidKeychain
         -25300 => ErrorCode::NoEntry,              // errSecItemNotFound
+        -25308 => ErrorCode::NoInteraction,        // errSecInteractionNotAllowed
         _ => ErrorCode::PlatformFailure(err),
     }
 }
@@ -79,6 +746,113 @@ fn decode_error(err: Error) -> ErrorCode {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_codes_round_trip() {
+        let name = format!("keyring-rs test codes {:?}", std::time::SystemTime::now());
+        let map = MacCredential {
+            domain: MacKeychainDomain::User,
+            service: name.clone(),
+            account: name,
+            creator_code: Some(*b"kyrs"),
+            type_code: Some(*b"test"),
+            comment: Some("a comment".to_string()),
+        };
+        let mut credential = PlatformCredential::Mac(map.clone());
+        set_password(&credential, "codes").unwrap();
+        assert_eq!(get_password(&mut credential).unwrap(), "codes");
+        assert_eq!(credential, PlatformCredential::Mac(map));
+        delete_password(&credential).unwrap();
+    }
+
+    #[test]
+    fn test_scoped_crate_keychain() {
+        use security_framework::os::macos::keychain::CreateOptions;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scoped.keychain");
+        let mut keychain = CreateOptions::new()
+            .password("scoped")
+            .create(&path)
+            .unwrap();
+        keychain.unlock(Some("scoped")).unwrap();
+        let name = format!("keyring-rs test scoped {:?}", std::time::SystemTime::now());
+        let credential = crate::credential::default_target(&platform(), None, &name, &name);
+        {
+            let _guard = ScopedCrateKeychain::new(&path).unwrap();
+            set_password(&credential, "isolated").unwrap();
+            let (password, _) =
+                find_generic_password(Some(&[keychain.clone()]), &name, &name).unwrap();
+            assert_eq!(&*password, b"isolated");
+        }
+        // with the guard gone, the credential isn't in the default keychain
+        assert!(matches!(
+            get_password(&mut credential.clone()),
+            Err(ErrorCode::NoEntry)
+        ));
+        assert!(ScopedCrateKeychain::new(&dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_open_keychain_file() {
+        use security_framework::os::macos::keychain::CreateOptions;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("import.keychain");
+        let mut keychain = CreateOptions::new()
+            .password("import")
+            .create(&path)
+            .unwrap();
+        keychain.unlock(Some("import")).unwrap();
+        keychain
+            .set_generic_password("import service", "import user", b"imported")
+            .unwrap();
+        let credentials = open_keychain_file(&path, "import").unwrap();
+        assert_eq!(credentials.len(), 1);
+        assert_eq!(credentials[0].service, "import service");
+        assert_eq!(credentials[0].account, "import user");
+        let _guard = ScopedCrateKeychain::new(&path).unwrap();
+        let mut credential = PlatformCredential::Mac(credentials[0].clone());
+        assert_eq!(get_password(&mut credential).unwrap(), "imported");
+        assert!(open_keychain_file(&dir.path().join("missing"), "import").is_err());
+    }
+
+    #[test]
+    fn test_error_messages() {
+        let message = error_message(&Error::from_code(-25300)).unwrap();
+        assert!(!message.is_empty());
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        assert!(decode_error(Error::from_code(-25291)).is_transient());
+        assert!(decode_error(Error::from_code(-25308)).is_transient());
+        assert!(!decode_error(Error::from_code(-128)).is_transient());
+        assert!(!decode_error(Error::from_code(-25299)).is_transient());
+        assert!(!decode_error(Error::from_code(-25292)).is_transient());
+    }
+
+    #[test]
+    fn test_read_only_errors() {
+        for code in [-61, -25292] {
+            assert!(matches!(
+                decode_error(Error::from_code(code)),
+                ErrorCode::ReadOnly(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn test_canceled_prompt() {
+        assert!(matches!(
+            decode_error(Error::from_code(-128)),
+            ErrorCode::UserCanceled(_)
+        ));
+        assert!(matches!(
+            decode_error(Error::from_code(-25308)),
+            ErrorCode::NoInteraction
+        ));
+    }
+
     #[test]
     fn test_bad_password() {
         // malformed sequences here taken from:
@@ -0,0 +1,116 @@
+/*
+Error injection, for testing how an application handles the errors that
+secure storage can report.  Most of those errors (such as a locked store,
+or a denied access) are hard to provoke on demand, so this module lets a
+test arrange for an entry operation to fail with a chosen error instead
+of reaching the platform at all.
+
+An injected error is used once: it is returned by the first operation
+it applies to, and then discarded.  An error injected for the next
+operation applies to the next operation of any entry on the injecting
+thread, so concurrently running tests don't see each other's errors.
+An error injected for a credential applies to the next operation on
+that credential from any thread, and takes effect after any error
+injected for the next operation.
+
+The operations that consult injected errors are the `Entry` methods
+that access the entry's credential: setting, getting, and deleting
+its password, and getting its metadata.
+
+This module is only available with the `mock` feature.
+ */
+
+use std::cell::RefCell;
+use std::sync::Mutex;
+
+use crate::credential::PlatformCredential;
+use crate::Error;
+
+thread_local! {
+    static NEXT: RefCell<Option<Error>> = const { RefCell::new(None) };
+}
+
+static INJECTED: Mutex<Vec<(PlatformCredential, Error)>> = Mutex::new(Vec::new());
+
+// Make the next entry operation on this thread fail with the given error.
+// This replaces any error previously injected for the next operation.
+pub fn inject_error_next(err: Error) {
+    NEXT.with(|next| *next.borrow_mut() = Some(err));
+}
+
+// Make the next operation on an entry for the given credential fail with
+// the given error.  Injecting more than one error for a credential makes
+// that many operations fail, in the order the errors were injected.
+pub fn inject_error(credential: &PlatformCredential, err: Error) {
+    injected().push((credential.clone(), err));
+}
+
+// Discard this thread's injected error for the next operation,
+// and all the errors injected for the given credential.
+pub fn clear_injected_errors(credential: &PlatformCredential) {
+    NEXT.with(|next| next.borrow_mut().take());
+    injected().retain(|(target, _)| target != credential);
+}
+
+// Take the injected error that applies to an operation on the given
+// credential, if there is one.
+pub(crate) fn take_error(credential: &PlatformCredential) -> Option<Error> {
+    if let Some(err) = NEXT.with(|next| next.borrow_mut().take()) {
+        return Some(err);
+    }
+    let mut injected = injected();
+    let index = injected
+        .iter()
+        .position(|(target, _)| target == credential)?;
+    Some(injected.remove(index).1)
+}
+
+fn injected() -> std::sync::MutexGuard<'static, Vec<(PlatformCredential, Error)>> {
+    INJECTED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Entry;
+
+    // None of these tests reach secure storage: every operation they
+    // perform fails with an injected error first.
+
+    #[test]
+    fn test_inject_error_next() {
+        let entry = Entry::new("mock-test-next", "user");
+        inject_error_next(Error::NoEntry);
+        assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+        inject_error_next(Error::Cancelled);
+        inject_error_next(Error::WrongCredentialPlatform);
+        assert!(matches!(
+            entry.set_password("ignored"),
+            Err(Error::WrongCredentialPlatform)
+        ));
+    }
+
+    #[test]
+    fn test_inject_error_for_credential() {
+        let entry = Entry::new("mock-test-credential", "user");
+        let other = Entry::new("mock-test-credential", "other user");
+        let credential = entry.target.clone();
+        inject_error(&credential, Error::TooLong("service".to_string(), 0));
+        inject_error(&credential, Error::Cancelled);
+        inject_error_next(Error::NoEntry);
+        // the next-operation error is used first, by whichever entry
+        assert!(matches!(other.get_metadata(), Err(Error::NoEntry)));
+        assert!(matches!(entry.get_metadata(), Err(Error::TooLong(_, 0))));
+        assert!(matches!(entry.delete_password(), Err(Error::Cancelled)));
+    }
+
+    #[test]
+    fn test_clear_injected_errors() {
+        let entry = Entry::new("mock-test-clear", "user");
+        let credential = entry.target.clone();
+        inject_error(&credential, Error::NoEntry);
+        inject_error_next(Error::NoEntry);
+        clear_injected_errors(&credential);
+        assert!(take_error(&credential).is_none());
+    }
+}
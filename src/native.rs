@@ -0,0 +1,180 @@
+/*
+A native export (see `Entry::export_native`) hands a single credential,
+with its password, to the platform's own command-line tool: it's the
+command that stores the same credential with that tool, so running it
+(on another machine, say) recreates the credential there.
+
+- Linux: `secret-tool store`, with the credential's label, collection
+  (if not the default) and attributes.  The password is given on the
+  tool's standard input.
+- Windows: `cmdkey /generic`, with the target name and username.  The
+  command ends with `/pass` and no password, so `cmdkey` prompts for
+  it, and the input is the answer.  `cmdkey` has no way to set a
+  comment or target alias, so those aren't exported.
+- Mac: `security add-generic-password -U`, with the service, account,
+  creator and type codes and comment.  The command ends with `-w` and
+  no password, so `security` prompts for it (twice, to confirm it),
+  and the input is the answers.  The item is added to the default
+  keychain of whoever runs the command, whichever domain it was
+  exported from.
+
+The password is never put on the command line, where other processes
+could see it while the command runs.  Since the export includes the
+password, its input is wiped when it's dropped (with the `zeroize`
+feature), and it never shows its command or input when debug-printed.
+ */
+
+use crate::credential::PlatformCredential;
+use crate::zeroing;
+
+pub struct NativeExport {
+    // The program to run, followed by its arguments.
+    pub command: Vec<String>,
+    // What to write to the program's standard input, if anything.
+    pub input: Option<String>,
+}
+
+impl Drop for NativeExport {
+    fn drop(&mut self) {
+        for arg in self.command.iter_mut() {
+            zeroing::wipe(arg);
+        }
+        if let Some(input) = self.input.as_mut() {
+            zeroing::wipe(input);
+        }
+    }
+}
+
+// The command and input may hold the password, so only the program is shown.
+impl std::fmt::Debug for NativeExport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NativeExport")
+            .field("program", &self.command.first())
+            .finish_non_exhaustive()
+    }
+}
+
+pub(crate) fn export(credential: &PlatformCredential, password: &str) -> NativeExport {
+    let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    match credential {
+        PlatformCredential::Linux(cred) => {
+            let mut command = args(&["secret-tool", "store"]);
+            command.push(format!("--label={}", cred.label));
+            if cred.collection != "default" {
+                command.push(format!("--collection={}", cred.collection));
+            }
+            let mut attributes: Vec<_> = cred.attributes.iter().collect();
+            attributes.sort();
+            for (name, value) in attributes {
+                command.push(name.clone());
+                command.push(value.clone());
+            }
+            NativeExport {
+                command,
+                input: Some(password.to_string()),
+            }
+        }
+        PlatformCredential::Win(cred) => NativeExport {
+            command: vec![
+                "cmdkey".to_string(),
+                format!("/generic:{}", cred.target_name),
+                format!("/user:{}", cred.username),
+                "/pass".to_string(),
+            ],
+            input: Some(format!("{}\n", password)),
+        },
+        PlatformCredential::Mac(cred) => {
+            let mut command = args(&["security", "add-generic-password", "-U"]);
+            command.extend(args(&["-s", &cred.service, "-a", &cred.account]));
+            let codes = [("-c", cred.creator_code), ("-C", cred.type_code)];
+            for (flag, code) in codes {
+                if let Some(code) = code {
+                    command.push(flag.to_string());
+                    command.push(String::from_utf8_lossy(&code).into_owned());
+                }
+            }
+            if let Some(comment) = &cred.comment {
+                command.extend(args(&["-j", comment]));
+            }
+            command.push("-w".to_string());
+            NativeExport {
+                command,
+                input: Some(format!("{}\n{}\n", password, password)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credential::{LinuxCredential, MacCredential, MacKeychainDomain, WinCredential};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_export() {
+        let linux = PlatformCredential::Linux(LinuxCredential {
+            collection: "login".to_string(),
+            attributes: HashMap::from([
+                ("service".to_string(), "svc".to_string()),
+                ("username".to_string(), "user".to_string()),
+            ]),
+            label: "a label".to_string(),
+        });
+        let exported = export(&linux, "secret");
+        assert_eq!(
+            exported.command,
+            [
+                "secret-tool",
+                "store",
+                "--label=a label",
+                "--collection=login",
+                "service",
+                "svc",
+                "username",
+                "user"
+            ]
+        );
+        assert_eq!(exported.input.as_deref(), Some("secret"));
+        let win = PlatformCredential::Win(WinCredential {
+            username: "user".to_string(),
+            target_name: "user.svc".to_string(),
+            target_alias: String::new(),
+            comment: String::new(),
+        });
+        let exported = export(&win, "secret");
+        assert_eq!(
+            exported.command,
+            ["cmdkey", "/generic:user.svc", "/user:user", "/pass"]
+        );
+        assert_eq!(exported.input.as_deref(), Some("secret\n"));
+        let mac = PlatformCredential::Mac(MacCredential {
+            domain: MacKeychainDomain::User,
+            service: "svc".to_string(),
+            account: "user".to_string(),
+            creator_code: Some(*b"abcd"),
+            type_code: None,
+            comment: Some("a comment".to_string()),
+        });
+        let exported = export(&mac, "secret");
+        assert_eq!(
+            exported.command,
+            [
+                "security",
+                "add-generic-password",
+                "-U",
+                "-s",
+                "svc",
+                "-a",
+                "user",
+                "-c",
+                "abcd",
+                "-j",
+                "a comment",
+                "-w"
+            ]
+        );
+        assert_eq!(exported.input.as_deref(), Some("secret\nsecret\n"));
+        assert!(!format!("{:?}", exported).contains("secret"));
+    }
+}
@@ -0,0 +1,86 @@
+/*
+Entries can be configured (see `Config::with_prompt_interval`) to limit
+how often the platform may prompt the user, so that a bulk operation
+doesn't bombard them with dialogs.  Each operation on secure storage by
+such an entry is first tried with user interaction turned off.  If it
+needs a prompt, it's tried again with interaction allowed, unless the
+user has been prompted within the interval, in which case it fails with
+`NoInteraction` instead.
+
+While an entry is limiting prompts, its operations on secure storage are
+serialized with those of every other such entry in the process, so
+prompts are never stacked on top of each other.
+
+Only Mac can be kept from prompting: the secret service prompts (to
+unlock a collection) on its own terms, and Windows never prompts.  So on
+those platforms the operations are just serialized.
+ */
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{platform, Error, Result};
+
+// When the user was last prompted on behalf of an entry limiting prompts.
+static LAST_PROMPT: Mutex<Option<Instant>> = Mutex::new(None);
+
+// Run an operation on secure storage, allowing it to prompt the user only
+// if they haven't been prompted within the interval.  Without an interval,
+// the operation is just run.
+pub(crate) fn limited<T>(
+    interval: Option<Duration>,
+    operation: impl Fn() -> Result<T>,
+) -> Result<T> {
+    let interval = match interval {
+        Some(interval) => interval,
+        None => return operation(),
+    };
+    let mut last_prompt = LAST_PROMPT.lock().unwrap_or_else(|e| e.into_inner());
+    match platform::without_interaction(&operation) {
+        Err(Error::NoInteraction) => {
+            if last_prompt.is_some_and(|last| last.elapsed() < interval) {
+                return Err(Error::NoInteraction);
+            }
+            *last_prompt = Some(Instant::now());
+            operation()
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An operation that needs to prompt the first time it's run.
+    fn needs_prompt() -> impl Fn() -> Result<&'static str> {
+        let runs = std::cell::Cell::new(0);
+        move || {
+            runs.set(runs.get() + 1);
+            match runs.get() {
+                1 => Err(Error::NoInteraction),
+                _ => Ok("prompted"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_prompts_limited() {
+        let interval = Some(Duration::from_secs(60));
+        assert!(matches!(limited(None, || Ok("run")), Ok("run")));
+        assert!(matches!(limited(interval, || Ok("run")), Ok("run")));
+        *LAST_PROMPT.lock().unwrap() = None;
+        assert!(matches!(limited(interval, needs_prompt()), Ok("prompted")));
+        // too soon to prompt again
+        assert!(matches!(
+            limited(interval, needs_prompt()),
+            Err(Error::NoInteraction)
+        ));
+        // but operations that don't prompt go ahead
+        assert!(matches!(limited(interval, || Ok("run")), Ok("run")));
+        assert!(matches!(
+            limited(Some(Duration::ZERO), needs_prompt()),
+            Ok("prompted")
+        ));
+    }
+}
@@ -0,0 +1,169 @@
+/*
+A password can be stored with an access schedule (see
+`Entry::set_password_with_schedule`), giving the days of the week and
+the hours of the day during which it may be read: say, a CI token that
+should only be usable during business hours.  Reading the password
+outside its schedule fails with `OutsideSchedule`.
+
+The schedule is enforced by the crate, not the platform, so it only
+binds entries configured to enforce schedules (see
+`Config::with_enforced_schedules`); other entries, and other programs,
+can read the password at any time.  It's a guard against mistakes, not
+an access control.
+
+The schedule is kept in the credential's schedule companion.  Setting
+the password again without a schedule removes the schedule, as does
+deleting the password (through any entry), so a schedule never
+outlives the password it was stored with.
+
+Days and hours are in UTC, since the crate has no notion of time zones.
+ */
+
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::credential::PlatformCredential;
+use crate::{platform, Error, Result};
+
+pub(crate) const PURPOSE: &str = "schedule";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Monday,
+    Weekday::Tuesday,
+    Weekday::Wednesday,
+    Weekday::Thursday,
+    Weekday::Friday,
+    Weekday::Saturday,
+    Weekday::Sunday,
+];
+
+// The times at which a password may be read: during the given hours
+// (UTC, from the start of the first to the start of the last, so `9..17`
+// is 9am to 5pm) of the given days.  A schedule whose hours are empty,
+// or that has no days, never allows the password to be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessSchedule {
+    pub days: Vec<Weekday>,
+    pub hours: Range<u32>,
+}
+
+impl AccessSchedule {
+    // Whether the schedule allows reading the password at the given time.
+    pub fn allows(&self, time: SystemTime) -> bool {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or(0);
+        // the epoch was a Thursday
+        let weekday = WEEKDAYS[((secs / 86_400 + 3) % 7) as usize];
+        let hour = (secs % 86_400 / 3600) as u32;
+        self.days.contains(&weekday) && self.hours.contains(&hour)
+    }
+
+    // The schedule is stored as a mask of the days it allows (one digit
+    // per day, from Monday) and its range of hours, as in `1111100 9-17`.
+    fn format(&self) -> String {
+        let days: String = WEEKDAYS
+            .iter()
+            .map(|day| if self.days.contains(day) { '1' } else { '0' })
+            .collect();
+        format!("{} {}-{}", days, self.hours.start, self.hours.end)
+    }
+
+    fn parse(stored: &str) -> Option<AccessSchedule> {
+        let (days, hours) = stored.trim_end().split_once(' ')?;
+        if days.len() != 7 || !days.bytes().all(|b| b == b'0' || b == b'1') {
+            return None;
+        }
+        let days = WEEKDAYS
+            .iter()
+            .zip(days.bytes())
+            .filter(|(_, allowed)| *allowed == b'1')
+            .map(|(day, _)| *day)
+            .collect();
+        let (start, end) = hours.split_once('-')?;
+        Some(AccessSchedule {
+            days,
+            hours: start.parse().ok()?..end.parse().ok()?,
+        })
+    }
+}
+
+// Read the schedule of a credential, if it has one.  A schedule that
+// can't be parsed fails with `BadFormat`, rather than allowing access.
+pub(crate) fn read(target: &PlatformCredential) -> Result<Option<AccessSchedule>> {
+    match platform::get_password(&mut target.companion(PURPOSE)) {
+        Ok(stored) => match AccessSchedule::parse(&stored) {
+            Some(schedule) => Ok(Some(schedule)),
+            None => Err(Error::BadFormat(format!("access schedule '{}'", stored))),
+        },
+        Err(Error::NoEntry) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+pub(crate) fn write(target: &PlatformCredential, schedule: &AccessSchedule) -> Result<()> {
+    platform::set_password(&target.companion(PURPOSE), &schedule.format())
+}
+
+// Remove the schedule of a credential, if it has one.
+pub(crate) fn delete(target: &PlatformCredential) -> Result<()> {
+    match platform::delete_password(&target.companion(PURPOSE)) {
+        Ok(()) | Err(Error::NoEntry) => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // 2021-03-04T05:06:07Z, a Thursday
+    fn thursday_morning() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_614_834_367)
+    }
+
+    #[test]
+    fn test_allows() {
+        let business_hours = AccessSchedule {
+            days: WEEKDAYS[..5].to_vec(),
+            hours: 9..17,
+        };
+        let thursday = thursday_morning();
+        assert!(!business_hours.allows(thursday));
+        assert!(business_hours.allows(thursday + Duration::from_secs(4 * 3600)));
+        assert!(!business_hours.allows(thursday + Duration::from_secs(12 * 3600)));
+        // Saturday afternoon
+        assert!(!business_hours.allows(thursday + Duration::from_secs(2 * 86_400 + 6 * 3600)));
+        let never = AccessSchedule {
+            days: Vec::new(),
+            hours: 0..24,
+        };
+        assert!(!never.allows(thursday));
+    }
+
+    #[test]
+    fn test_format_round_trip() {
+        let schedule = AccessSchedule {
+            days: vec![Weekday::Monday, Weekday::Sunday],
+            hours: 22..24,
+        };
+        assert_eq!(schedule.format(), "1000001 22-24");
+        assert_eq!(AccessSchedule::parse(&schedule.format()), Some(schedule));
+        assert!(AccessSchedule::parse("1111100").is_none());
+        assert!(AccessSchedule::parse("11111 9-17").is_none());
+        assert!(AccessSchedule::parse("1111100 nine-17").is_none());
+    }
+}
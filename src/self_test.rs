@@ -0,0 +1,69 @@
+/*
+A self test, for readiness checks, that proves the platform's secure
+storage works end to end: a throwaway credential is written, read back,
+and deleted, and then looked for again to make sure it's gone.  This
+catches storage that can be read but not written (or written but not
+deleted), which merely connecting to it wouldn't.
+
+The test goes straight to the platform's default store, so it isn't
+affected by the global configuration's hooks (a confirmation hook
+can't keep the credential from being deleted, and a policy can't
+reject its password).  If any step after the write fails, the
+credential is deleted anyway, so it's never left behind.
+ */
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::credential::{default_target, PlatformCredential};
+use crate::{platform, Error, Result};
+
+const SERVICE: &str = "keyring-rs self-test";
+
+// Run the self test.  A step that fails is reported as a `WithContext`
+// error whose tag names the step (`self-test set`, `self-test get`,
+// `self-test delete` or `self-test verify`), wrapping the step's error.
+// A step that succeeds with the wrong result (the password read back
+// isn't the one written, or the credential is still there after being
+// deleted) fails with `SelfTestFailed`.
+pub fn self_test() -> Result<()> {
+    let target = throwaway();
+    let password = format!("self-test password for {}", target.username());
+    step("set", platform::set_password(&target, &password))?;
+    let checked = check(&target, &password);
+    if checked.is_err() {
+        let _ = platform::delete_password(&target);
+    }
+    checked
+}
+
+fn check(target: &PlatformCredential, password: &str) -> Result<()> {
+    let read = step("get", platform::get_password(&mut target.clone()))?;
+    if read != password {
+        return step(
+            "get",
+            Err(Error::SelfTestFailed("read back a different password")),
+        );
+    }
+    step("delete", platform::delete_password(target))?;
+    match platform::get_password(&mut target.clone()) {
+        Err(Error::NoEntry) => Ok(()),
+        Ok(_) => step(
+            "verify",
+            Err(Error::SelfTestFailed("still there after deletion")),
+        ),
+        Err(err) => step("verify", Err(err)),
+    }
+}
+
+// A credential that no other process (or other self test) is using.
+fn throwaway() -> PlatformCredential {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let username = format!("{}-{}", std::process::id(), now.as_nanos());
+    default_target(&crate::platform(), None, SERVICE, &username)
+}
+
+fn step<T>(name: &str, result: Result<T>) -> Result<T> {
+    result.map_err(|err| Error::WithContext(format!("self-test {}", name), Box::new(err)))
+}
@@ -0,0 +1,247 @@
+/*
+Credentials can be serialized to a string, so that an application can
+persist the credentials it uses (say, in its configuration) and load
+them again later.  Only the credential is serialized, never a password.
+
+The serialized form records the platform the credential is for, and
+loading a credential for a different platform than the running one
+fails with `WrongCredentialPlatform`, just as `Entry::new_with_credential`
+does.  So a credential persisted on one machine can't be silently
+misinterpreted on another.
+
+The format is line-oriented: a header line naming the format version
+and platform, then one `name=value` line per field.  Names and values
+are percent-escaped, so they can contain any characters.  Four-character
+codes are written in hex, since they needn't be text.
+ */
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+
+use crate::credential::{
+    LinuxCredential, MacCredential, MacKeychainDomain, Platform, PlatformCredential, WinCredential,
+};
+use crate::{Error, Result};
+
+const HEADER: &str = "keyring-rs credential v1";
+
+impl PlatformCredential {
+    // Serialize this credential, including its platform.
+    pub fn to_serialized(&self) -> String {
+        let mut fields: Vec<(String, String)> = Vec::new();
+        let platform = match self {
+            PlatformCredential::Linux(cred) => {
+                fields.push(("collection".to_string(), cred.collection.clone()));
+                fields.push(("label".to_string(), cred.label.clone()));
+                let mut attributes: Vec<_> = cred.attributes.iter().collect();
+                attributes.sort();
+                for (key, value) in attributes {
+                    fields.push((format!("attribute.{}", key), value.clone()));
+                }
+                "linux"
+            }
+            PlatformCredential::Win(cred) => {
+                fields.push(("username".to_string(), cred.username.clone()));
+                fields.push(("target_name".to_string(), cred.target_name.clone()));
+                fields.push(("target_alias".to_string(), cred.target_alias.clone()));
+                fields.push(("comment".to_string(), cred.comment.clone()));
+                "windows"
+            }
+            PlatformCredential::Mac(cred) => {
+                let domain = format!("{:?}", cred.domain).to_lowercase();
+                fields.push(("domain".to_string(), domain));
+                fields.push(("service".to_string(), cred.service.clone()));
+                fields.push(("account".to_string(), cred.account.clone()));
+                if let Some(code) = cred.creator_code {
+                    fields.push(("creator_code".to_string(), hex(&code)));
+                }
+                if let Some(code) = cred.type_code {
+                    fields.push(("type_code".to_string(), hex(&code)));
+                }
+                if let Some(comment) = &cred.comment {
+                    fields.push(("comment".to_string(), comment.clone()));
+                }
+                "macos"
+            }
+        };
+        let mut serialized = format!("{} {}\n", HEADER, platform);
+        for (name, value) in fields {
+            serialized.push_str(&format!("{}={}\n", escape(&name), escape(&value)));
+        }
+        serialized
+    }
+
+    // Load a serialized credential.  This fails with `WrongCredentialPlatform`
+    // if the credential is for a different platform than the running one,
+    // and with `BadFormat` if it can't be parsed.
+    pub fn from_serialized(serialized: &str) -> Result<PlatformCredential> {
+        let credential = parse(serialized)?;
+        if credential.matches_platform(&crate::platform()) {
+            Ok(credential)
+        } else {
+            Err(Error::WrongCredentialPlatform)
+        }
+    }
+}
+
+fn parse(serialized: &str) -> Result<PlatformCredential> {
+    let bad = |reason: &str| Error::BadFormat(format!("serialized credential: {}", reason));
+    let mut lines = serialized.lines();
+    let platform = match lines.next().and_then(|line| line.strip_prefix(HEADER)) {
+        Some(" linux") => Platform::Linux,
+        Some(" windows") => Platform::Windows,
+        Some(" macos") => Platform::MacOs,
+        _ => return Err(bad("unknown header")),
+    };
+    let mut fields = HashMap::new();
+    for line in lines.filter(|line| !line.is_empty()) {
+        let (name, value) = line.split_once('=').ok_or_else(|| bad("missing '='"))?;
+        let name = unescape(name).ok_or_else(|| bad("bad escape"))?;
+        let value = unescape(value).ok_or_else(|| bad("bad escape"))?;
+        if fields.insert(name.clone(), value).is_some() {
+            return Err(bad(&format!("repeated field '{}'", name)));
+        }
+    }
+    let mut take = |name: &str| {
+        fields
+            .remove(name)
+            .ok_or_else(|| bad(&format!("missing field '{}'", name)))
+    };
+    let credential = match platform {
+        Platform::Linux => {
+            let collection = take("collection")?;
+            let label = take("label")?;
+            let mut attributes = HashMap::new();
+            for (name, value) in fields.drain() {
+                match name.strip_prefix("attribute.") {
+                    Some(key) => attributes.insert(key.to_string(), value),
+                    None => return Err(bad(&format!("unknown field '{}'", name))),
+                };
+            }
+            PlatformCredential::Linux(LinuxCredential {
+                collection,
+                attributes,
+                label,
+            })
+        }
+        Platform::Windows => PlatformCredential::Win(WinCredential {
+            username: take("username")?,
+            target_name: take("target_name")?,
+            target_alias: take("target_alias")?,
+            comment: take("comment")?,
+        }),
+        Platform::MacOs => {
+            let domain = MacKeychainDomain::from(take("domain")?.as_str());
+            let service = take("service")?;
+            let account = take("account")?;
+            let mut code = |name: &str| match fields.remove(name) {
+                Some(value) => unhex(&value)
+                    .and_then(|code| code.try_into().ok())
+                    .map(Some)
+                    .ok_or_else(|| bad(&format!("bad field '{}'", name))),
+                None => Ok(None),
+            };
+            PlatformCredential::Mac(MacCredential {
+                domain,
+                service,
+                account,
+                creator_code: code("creator_code")?,
+                type_code: code("type_code")?,
+                comment: fields.remove("comment"),
+            })
+        }
+    };
+    if let PlatformCredential::Win(_) | PlatformCredential::Mac(_) = credential {
+        if let Some(name) = fields.keys().next() {
+            return Err(bad(&format!("unknown field '{}'", name)));
+        }
+    }
+    Ok(credential)
+}
+
+pub(crate) fn escape(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '%' | '=' | '\n' | '\r' => escaped.push_str(&format!("%{:02X}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub(crate) fn unescape(value: &str) -> Option<String> {
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '%' {
+            let code: String = chars.by_ref().take(2).collect();
+            unescaped.push(u8::from_str_radix(&code, 16).ok()? as char);
+        } else {
+            unescaped.push(c);
+        }
+    }
+    Some(unescaped)
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(crate) fn unhex(value: &str) -> Option<Vec<u8>> {
+    if !value.len().is_multiple_of(2) || !value.is_ascii() {
+        return None;
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::credential::default_target;
+
+    #[test]
+    fn test_round_trip_every_platform() {
+        for platform in [Platform::Linux, Platform::Windows, Platform::MacOs] {
+            let mut credential = default_target(&platform, None, "svc=1%\n", "user\r\n");
+            if let PlatformCredential::Mac(cred) = &mut credential {
+                cred.creator_code = Some(*b"\xffab=");
+                cred.comment = Some("a=comment".to_string());
+            }
+            assert_eq!(parse(&credential.to_serialized()).unwrap(), credential);
+        }
+    }
+
+    #[test]
+    fn test_wrong_platform_rejected() {
+        let this = default_target(&crate::platform(), None, "service", "user");
+        let loaded = PlatformCredential::from_serialized(&this.to_serialized()).unwrap();
+        assert_eq!(loaded, this);
+        for platform in [Platform::Linux, Platform::Windows, Platform::MacOs] {
+            let other = default_target(&platform, None, "service", "user");
+            if !other.matches_platform(&crate::platform()) {
+                assert!(matches!(
+                    PlatformCredential::from_serialized(&other.to_serialized()),
+                    Err(Error::WrongCredentialPlatform)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn test_malformed_rejected() {
+        for serialized in [
+            "",
+            "keyring-rs credential v2 linux\n",
+            "keyring-rs credential v1 windows\nusername=u\n",
+            "keyring-rs credential v1 windows\nusername\n",
+            "keyring-rs credential v1 macos\ndomain=user\nservice=s\naccount=a\ntype_code=xyz\n",
+            "keyring-rs credential v1 linux\ncollection=c\nlabel=l\ncolour=red\n",
+        ] {
+            assert!(matches!(parse(serialized), Err(Error::BadFormat(_))));
+        }
+    }
+}
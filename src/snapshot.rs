@@ -0,0 +1,86 @@
+/*
+A snapshot is a point-in-time copy of the credentials (in the platform's
+default store) whose service starts with a prefix, with their metadata,
+held in memory: a report or backup can go through it at leisure and see
+a consistent view, however the store changes in the meantime.
+
+The credentials and their metadata are listed in a single pass (see
+`enumerate_with_metadata`).  By default a snapshot has no passwords;
+`snapshot_with_secrets` also reads the password of each credential
+right after listing them, which may prompt the user for each one.  A
+credential that is deleted between being listed and having its password
+read is left out, since it's no longer there by the time the snapshot
+is complete.
+
+A snapshot's passwords are read straight from the platform, so they
+aren't affected by any entry's configuration (there are no fallbacks,
+provisioning or access logging).
+ */
+
+use std::time::SystemTime;
+
+use crate::credential::{CredentialMetadata, PlatformCredential};
+use crate::{platform, Error, Result};
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    // When the credentials were listed.
+    pub taken: SystemTime,
+    // The credentials, in the order the platform listed them.
+    pub items: Vec<SnapshotItem>,
+}
+
+#[derive(Clone)]
+pub struct SnapshotItem {
+    pub credential: PlatformCredential,
+    pub metadata: CredentialMetadata,
+    // The password, if the snapshot was taken with secrets.
+    pub password: Option<String>,
+}
+
+// Passwords are never shown, so they can't leak through logging.
+impl std::fmt::Debug for SnapshotItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SnapshotItem")
+            .field("credential", &self.credential)
+            .field("metadata", &self.metadata)
+            .field("password", &self.password.as_ref().map(|_| ".."))
+            .finish()
+    }
+}
+
+// Take a snapshot, without passwords, of the credentials whose service
+// starts with the given prefix.
+pub fn snapshot(prefix: &str) -> Result<Snapshot> {
+    let taken = SystemTime::now();
+    let items = crate::enumerate_with_metadata(prefix)?
+        .into_iter()
+        .map(|(credential, metadata)| SnapshotItem {
+            credential,
+            metadata,
+            password: None,
+        })
+        .collect();
+    Ok(Snapshot { taken, items })
+}
+
+// Take a snapshot of the credentials whose service starts with the given
+// prefix, with their passwords.  If any password can't be read (other
+// than because its credential has been deleted), the snapshot fails with
+// that error.
+pub fn snapshot_with_secrets(prefix: &str) -> Result<Snapshot> {
+    let mut snapshot = snapshot(prefix)?;
+    let mut items = Vec::with_capacity(snapshot.items.len());
+    for mut item in snapshot.items {
+        match platform::get_password(&mut item.credential.clone()) {
+            Ok(password) => {
+                item.password = Some(password);
+                items.push(item);
+            }
+            Err(Error::NoEntry) => {}
+            Err(err) => return Err(err),
+        }
+    }
+    snapshot.items = items;
+    Ok(snapshot)
+}
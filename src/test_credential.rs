@@ -0,0 +1,40 @@
+/*
+A test credential is an entry for a test to store a password in, which
+deletes its password when it's dropped, so a test leaves nothing behind
+in secure storage even if it fails (or panics).  It works the same
+whatever the backend, and needs no feature, so the same tests can run
+against secure storage with or without the `mock` feature's injected
+errors.
+ */
+
+use crate::Entry;
+
+// An entry, with the given name as both its service and username, whose
+// password (if it has one) is deleted when it's dropped.  The name should
+// be unique to the test (random, say), so tests running at the same time
+// don't share a credential.
+pub struct TestCredential {
+    entry: Entry,
+}
+
+impl TestCredential {
+    pub fn new(name: &str) -> TestCredential {
+        TestCredential {
+            entry: Entry::new(name, name),
+        }
+    }
+}
+
+impl std::ops::Deref for TestCredential {
+    type Target = Entry;
+
+    fn deref(&self) -> &Entry {
+        &self.entry
+    }
+}
+
+impl Drop for TestCredential {
+    fn drop(&mut self) {
+        let _ = self.entry.delete_password();
+    }
+}
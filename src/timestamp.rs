@@ -0,0 +1,128 @@
+// ISO-8601 formatting of times, so that the times this crate reports
+// are readable (and sortable) without pulling in a date-time library.
+// All times are in UTC with a precision of one second.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Format a time as, for example, `2021-03-04T05:06:07Z`.  Times before
+// the epoch are formatted as the epoch.
+pub(crate) fn format(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0);
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+// Parse a time in the format produced by `format`.
+pub(crate) fn parse(text: &str) -> Option<SystemTime> {
+    // checked first, so the slices below are on character boundaries
+    if !text.is_ascii()
+        || text.len() != 20
+        || &text[4..5] != "-"
+        || &text[7..8] != "-"
+        || &text[10..11] != "T"
+    {
+        return None;
+    }
+    if &text[13..14] != ":" || &text[16..17] != ":" || &text[19..] != "Z" {
+        return None;
+    }
+    let number = |range: std::ops::Range<usize>| -> Option<u64> {
+        let digits = text.get(range)?;
+        digits
+            .bytes()
+            .all(|b| b.is_ascii_digit())
+            .then(|| digits.parse().ok())?
+    };
+    let (year, month, day) = (number(0..4)?, number(5..7)?, number(8..10)?);
+    let (hour, minute, second) = (number(11..13)?, number(14..16)?, number(17..19)?);
+    if year < 1970 || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let days = days_from_civil(year as i64, month as u32, day as u32);
+    let secs = days as u64 * 86_400 + hour * 3600 + minute * 60 + second;
+    let time = UNIX_EPOCH + std::time::Duration::from_secs(secs);
+    // reject dates (like February 30) that don't exist
+    (format(time) == text).then_some(time)
+}
+
+// Convert a (year, month, day) date to days since the epoch, using the
+// algorithm from http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+// Convert days since the epoch to a (year, month, day) date, using
+// the algorithm from http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format() {
+        for (secs, expected) in [
+            (0, "1970-01-01T00:00:00Z"),
+            (951_827_696, "2000-02-29T12:34:56Z"),
+            (1_609_459_199, "2020-12-31T23:59:59Z"),
+        ] {
+            assert_eq!(format(UNIX_EPOCH + Duration::from_secs(secs)), expected);
+            assert_eq!(
+                parse(expected),
+                Some(UNIX_EPOCH + Duration::from_secs(secs))
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed() {
+        for text in [
+            "",
+            "2021-02-30T00:00:00Z",
+            "2021-13-01T00:00:00Z",
+            "2021-01-01T24:00:00Z",
+            "2021-01-01 00:00:00Z",
+            "2021-01-01T00:00:00",
+            "+021-01-01T00:00:00Z",
+            "1969-12-31T23:59:59Z",
+            // 20 bytes, but not all ASCII
+            "202é01-01T00:00:00Z",
+            "2021-01-01T00:00:0é",
+        ] {
+            assert_eq!(parse(text), None, "{}", text);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+/*
+A TOTP entry keeps the seed of a time-based one-time password (the
+shared secret an authenticator app generates codes from) in an entry,
+with the issuer and account the seed is for.
+
+Seeds are given in base32 (RFC 4648), as they are in `otpauth://` URIs
+and in the text setup codes services show: letters are taken in either
+case, and spaces, dashes and padding are ignored.  The seed is stored as
+the entry's password in canonical form (upper case, without separators
+or padding), and `load_seed` decodes it to the raw seed bytes.  A seed
+that isn't valid base32, when stored or when loaded, fails with
+`BadFormat` (whose reason never includes the seed).
+
+The issuer and account are kept in a companion credential of the
+entry's own credential, so they can be read back (see `load_label`)
+without reading the seed.
+ */
+
+use crate::serialized::{escape, unescape};
+use crate::{platform, Entry, Error, Result};
+
+pub(crate) const PURPOSE: &str = "totp";
+const ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+#[derive(Debug, Clone)]
+pub struct TotpEntry {
+    entry: Entry,
+}
+
+impl TotpEntry {
+    // Keep a TOTP seed in the given entry.
+    pub fn new(entry: Entry) -> TotpEntry {
+        TotpEntry { entry }
+    }
+
+    // The entry the seed is kept in.
+    pub fn entry(&self) -> &Entry {
+        &self.entry
+    }
+
+    // Store the base32 seed, for the given issuer and account, replacing
+    // any seed stored before.
+    pub fn store_seed(&self, issuer: &str, account: &str, base32_seed: &str) -> Result<()> {
+        let seed = canonical(base32_seed)?;
+        let label = format!("{}={}", escape(issuer), escape(account));
+        self.entry.tagged(|| {
+            self.entry.injected_error()?;
+            let companion = self.entry.target.companion(PURPOSE);
+            self.entry
+                .limited(|| platform::set_password(&companion, &label))
+        })?;
+        self.entry.set_password(&seed)
+    }
+
+    // Read the stored seed, as raw bytes.
+    pub fn load_seed(&self) -> Result<Vec<u8>> {
+        let seed = self.entry.get_password()?;
+        decode(&seed).ok_or_else(bad_seed)
+    }
+
+    // Read the issuer and account the stored seed is for.
+    pub fn load_label(&self) -> Result<(String, String)> {
+        self.entry.tagged(|| {
+            self.entry.injected_error()?;
+            let companion = self.entry.target.companion(PURPOSE);
+            let stored = self
+                .entry
+                .limited(|| platform::get_password(&mut companion.clone()))?;
+            let malformed = || Error::BadFormat(format!("TOTP label '{}'", stored));
+            let (issuer, account) = stored.split_once('=').ok_or_else(malformed)?;
+            Ok((
+                unescape(issuer).ok_or_else(malformed)?,
+                unescape(account).ok_or_else(malformed)?,
+            ))
+        })
+    }
+
+    // Delete the stored seed, and its issuer and account.
+    pub fn delete_seed(&self) -> Result<()> {
+        self.entry.delete_password()?;
+        self.entry.tagged(|| {
+            match platform::delete_password(&self.entry.target.companion(PURPOSE)) {
+                Ok(()) | Err(Error::NoEntry) => Ok(()),
+                Err(err) => Err(err),
+            }
+        })
+    }
+}
+
+fn bad_seed() -> Error {
+    Error::BadFormat("TOTP seed isn't valid base32".to_string())
+}
+
+// The canonical form of a base32 seed, if it's valid.
+fn canonical(base32_seed: &str) -> Result<String> {
+    let seed: String = base32_seed
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '='))
+        .map(|c| c.to_ascii_uppercase())
+        .collect();
+    match decode(&seed) {
+        Some(bytes) if !bytes.is_empty() => Ok(seed),
+        _ => Err(bad_seed()),
+    }
+}
+
+// Decode canonical base32.  Bits left over at the end (fewer than a
+// byte's worth) must be zero, as they are in any encoded seed.
+fn decode(seed: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(seed.len() * 5 / 8);
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in seed.bytes() {
+        let value = ALPHABET.iter().position(|&a| a == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    // five bits or more left over means a character that encodes nothing
+    (bits < 5 && buffer == 0).then_some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        // the RFC 6238 test seed, "12345678901234567890"
+        let seed = canonical("gezd gnbv gy3t qojq gezd gnbv gy3t qojq").unwrap();
+        assert_eq!(seed, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(decode(&seed).unwrap(), b"12345678901234567890");
+        assert_eq!(decode(&canonical("MZXW6===").unwrap()).unwrap(), b"foo");
+        assert_eq!(decode(&canonical("MZXW6YQ=").unwrap()).unwrap(), b"foob");
+    }
+
+    #[test]
+    fn test_malformed() {
+        for seed in ["", "====", "MZXW1", "MZXW6!", "MZXW7", "M"] {
+            assert!(
+                matches!(canonical(seed), Err(Error::BadFormat(_))),
+                "{}",
+                seed
+            );
+        }
+    }
+}
@@ -0,0 +1,189 @@
+/*
+Secure storage has no transactions, so an operation that sets several
+passwords can fail (or panic) part way through, leaving only some of
+them set.  A transaction (see `transaction`) limits the damage: it
+remembers each password it sets, and the secret that was stored
+before, and if the operation fails it puts the old secrets back and
+deletes the credentials it created.  The old secrets are saved and
+restored exactly as they were stored, so rolling back doesn't
+normalize, check or transform them again, and saving them isn't a
+retrieval (it isn't logged, and isn't subject to an access schedule).
+
+This is a best-effort compensation, not isolation.  Other processes can
+see (and change) the new passwords before they are rolled back, and
+rolling back overwrites any change they made in the meantime.  Rolling
+back can itself fail (deletions must be confirmed by the confirmation
+hook, if there is one), in which case the credentials are left as they
+are: the error returned is always the one the operation failed with.
+Only passwords are rolled back; other metadata, such as a Mac item's
+comment, keeps its new value.
+
+A batch (see `batch`) is the same thing for a list of operations given
+up front: the sets and deletes it's given are applied in order, as if
+in a transaction, and if one fails the ones already applied are rolled
+back.  Rather than an error, it returns a report of how far it got and
+whether the rollback succeeded.  The operations are applied one after
+another, without anything else from this process in between, so a
+store that must be unlocked is normally unlocked (with a prompt) by the
+first of them and stays unlocked for the rest; but the platforms have
+no way to authorize several operations at once, so one that prompts for
+each credential (as a Mac item's access control may) still does.
+ */
+
+use crate::{zeroing, Entry, Error, Result};
+
+// The writes made during a transaction, in the order they were made,
+// with the secret each replaced (if there was one).
+pub struct Transaction {
+    writes: Vec<(Entry, Option<Vec<u8>>)>,
+}
+
+impl Transaction {
+    // Set the password of an entry, remembering its current secret so
+    // the write can be rolled back.
+    pub fn set(&mut self, entry: &Entry, password: &str) -> Result<()> {
+        let previous = match entry.stored_secret() {
+            Ok(previous) => Some(previous),
+            Err(err) if matches!(err.inner(), Error::NoEntry) => None,
+            Err(err) => return Err(err),
+        };
+        entry.set_password(password)?;
+        self.writes.push((entry.clone(), previous));
+        Ok(())
+    }
+
+    // Delete the password of an entry, remembering its secret so the
+    // deletion can be rolled back.
+    pub fn delete(&mut self, entry: &Entry) -> Result<()> {
+        let previous = entry.stored_secret()?;
+        entry.delete_password()?;
+        self.writes.push((entry.clone(), Some(previous)));
+        Ok(())
+    }
+
+    pub(crate) fn new() -> Transaction {
+        Transaction { writes: Vec::new() }
+    }
+
+    // Keep the writes made so far, so they aren't rolled back.
+    pub(crate) fn keep(&mut self) {
+        for (_, mut previous) in self.writes.drain(..) {
+            if let Some(secret) = previous.as_mut() {
+                zeroing::wipe(secret);
+            }
+        }
+    }
+
+    // Undo the writes, latest first, returning the entries whose writes
+    // couldn't be undone.  A write that can't be undone doesn't stop the
+    // others.
+    pub(crate) fn roll_back(&mut self) -> Vec<Entry> {
+        let mut not_undone = Vec::new();
+        while let Some((entry, previous)) = self.writes.pop() {
+            let result = match previous {
+                Some(mut secret) => {
+                    let result = entry.restore_secret(&secret);
+                    zeroing::wipe(&mut secret);
+                    result
+                }
+                None => entry.delete_password(),
+            };
+            if result.is_err() {
+                not_undone.push(entry);
+            }
+        }
+        not_undone
+    }
+}
+
+// Rolling back when the transaction is dropped (rather than when the
+// operation returns) means the writes are rolled back on a panic, too.
+// Either way, the previous secrets it saved are wiped.
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        self.roll_back();
+    }
+}
+
+// Run an operation that sets passwords through the given transaction.
+// If the operation fails (or panics), the passwords it set are rolled
+// back, as well as possible, before its error is returned (or its panic
+// resumed).
+pub fn transaction<T>(operation: impl FnOnce(&mut Transaction) -> Result<T>) -> Result<T> {
+    let mut transaction = Transaction::new();
+    let result = operation(&mut transaction);
+    if result.is_ok() {
+        transaction.keep();
+    }
+    result
+}
+
+// The operations of a batch, in the order they were given.
+pub struct Batch {
+    operations: Vec<(Entry, Option<String>)>,
+}
+
+impl Batch {
+    // Set the password of an entry.
+    pub fn set(&mut self, entry: &Entry, password: &str) {
+        self.operations
+            .push((entry.clone(), Some(password.to_string())));
+    }
+
+    // Delete the password of an entry.
+    pub fn delete(&mut self, entry: &Entry) {
+        self.operations.push((entry.clone(), None));
+    }
+}
+
+impl Drop for Batch {
+    fn drop(&mut self) {
+        for (_, password) in self.operations.iter_mut() {
+            if let Some(password) = password.as_mut() {
+                zeroing::wipe(password);
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct BatchReport {
+    // How many of the operations were applied before one failed (all of
+    // them, if none did), whether or not they were then rolled back.
+    pub applied: usize,
+    // The error the failed operation failed with, if one did.
+    pub error: Option<Error>,
+    // Whether an operation failed and all those applied before it were
+    // rolled back.
+    pub rolled_back: bool,
+}
+
+// Apply the operations given to the batch by `build`, in order.  If one
+// fails, the operations already applied are rolled back as well as
+// possible, and the rest aren't attempted.
+pub fn batch(build: impl FnOnce(&mut Batch)) -> BatchReport {
+    let mut batch = Batch {
+        operations: Vec::new(),
+    };
+    build(&mut batch);
+    let mut transaction = Transaction::new();
+    for (applied, (entry, password)) in batch.operations.iter().enumerate() {
+        let result = match password {
+            Some(password) => transaction.set(entry, password),
+            None => transaction.delete(entry),
+        };
+        if let Err(err) = result {
+            return BatchReport {
+                applied,
+                error: Some(err),
+                rolled_back: transaction.roll_back().is_empty(),
+            };
+        }
+    }
+    transaction.keep();
+    BatchReport {
+        applied: batch.operations.len(),
+        error: None,
+        rolled_back: false,
+    }
+}
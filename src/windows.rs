@@ -3,20 +3,28 @@ use std::iter::once;
 use std::mem::MaybeUninit;
 use std::slice;
 use std::str;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use winapi::shared::minwindef::FILETIME;
 use winapi::shared::winerror::{
-    ERROR_BAD_USERNAME, ERROR_INVALID_FLAGS, ERROR_INVALID_PARAMETER, ERROR_NOT_FOUND,
-    ERROR_NO_SUCH_LOGON_SESSION,
+    ERROR_BAD_USERNAME, ERROR_CANCELLED, ERROR_INVALID_FLAGS, ERROR_INVALID_PARAMETER,
+    ERROR_NOT_FOUND, ERROR_NO_SUCH_LOGON_SESSION,
 };
 use winapi::um::errhandlingapi::GetLastError;
+use winapi::um::winbase::{
+    FormatMessageW, FORMAT_MESSAGE_FROM_SYSTEM, FORMAT_MESSAGE_IGNORE_INSERTS,
+};
 use winapi::um::wincred::{
-    CredDeleteW, CredFree, CredReadW, CredWriteW, CREDENTIALW, CRED_MAX_CREDENTIAL_BLOB_SIZE,
-    CRED_MAX_GENERIC_TARGET_NAME_LENGTH, CRED_MAX_STRING_LENGTH, CRED_MAX_USERNAME_LENGTH,
-    CRED_PERSIST_ENTERPRISE, CRED_TYPE_GENERIC, PCREDENTIALW, PCREDENTIAL_ATTRIBUTEW,
+    CredDeleteW, CredEnumerateW, CredFree, CredGetSessionTypes, CredReadW, CredWriteW, CREDENTIALW,
+    CRED_MAX_CREDENTIAL_BLOB_SIZE, CRED_MAX_GENERIC_TARGET_NAME_LENGTH, CRED_MAX_STRING_LENGTH,
+    CRED_MAX_USERNAME_LENGTH, CRED_PERSIST_ENTERPRISE, CRED_PERSIST_LOCAL_MACHINE,
+    CRED_PERSIST_SESSION, CRED_TYPE_GENERIC, CRED_TYPE_MAXIMUM, PCREDENTIALW,
+    PCREDENTIAL_ATTRIBUTEW,
 };
 
-use crate::credential::WinCredential;
-use crate::{Error as ErrorCode, Platform, PlatformCredential, Result};
+use crate::credential::{
+    parse_expiry, AccessControl, CredentialMetadata, WinCredential, WinPersistence,
+};
+use crate::{zeroing, Error as ErrorCode, Platform, PlatformCredential, Result};
 
 pub fn platform() -> Platform {
     Platform::Windows
@@ -31,6 +39,7 @@ impl std::fmt::Display for Error {
             ERROR_NO_SUCH_LOGON_SESSION => write!(f, "Windows ERROR_NO_SUCH_LOGON_SESSION"),
             ERROR_NOT_FOUND => write!(f, "Windows ERROR_NOT_FOUND"),
             ERROR_BAD_USERNAME => write!(f, "Windows ERROR_BAD_USERNAME"),
+            ERROR_CANCELLED => write!(f, "Windows ERROR_CANCELLED"),
             ERROR_INVALID_FLAGS => write!(f, "Windows ERROR_INVALID_FLAGS"),
             ERROR_INVALID_PARAMETER => write!(f, "Windows ERROR_INVALID_PARAMETER"),
             err => write!(f, "Windows error code {}", err),
@@ -44,24 +53,75 @@ impl std::error::Error for Error {
     }
 }
 
+// The credential manager never prompts, so operations are just run.
+pub fn without_interaction<T>(operation: impl FnOnce() -> Result<T>) -> Result<T> {
+    operation()
+}
+
+// Whether an error is one that may go away if the operation is retried.
+// Services starting up or switching logon sessions transiently find that
+// there is no logon session (and so no credential store) yet.
+pub fn is_transient(err: &ErrorCode) -> bool {
+    matches!(
+        err,
+        ErrorCode::NoStorageAccess(Error(ERROR_NO_SUCH_LOGON_SESSION))
+    )
+}
+
+// The system's message for an error code (from `FormatMessage`), in the
+// user's language.  System messages end with a line break, which is dropped.
+pub fn error_message(err: &Error) -> Option<String> {
+    let mut buffer = [0u16; 512];
+    let len = unsafe {
+        FormatMessageW(
+            FORMAT_MESSAGE_FROM_SYSTEM | FORMAT_MESSAGE_IGNORE_INSERTS,
+            std::ptr::null(),
+            err.0,
+            0,
+            buffer.as_mut_ptr(),
+            buffer.len() as u32,
+            std::ptr::null_mut(),
+        )
+    };
+    let message = String::from_utf16_lossy(&buffer[..len as usize]);
+    let message = message.trim_end();
+    (!message.is_empty()).then(|| message.to_string())
+}
+
 // DWORD is u32
 // LPCWSTR is *const u16
 // BOOL is i32 (false = 0, true = 1)
 // PCREDENTIALW = *mut CREDENTIALW
+// The credential store always exists, so there is nothing to create.
+pub fn ensure_store(_map: &PlatformCredential) -> Result<()> {
+    Ok(())
+}
+
 pub fn set_password(map: &PlatformCredential, password: &str) -> Result<()> {
+    // Password strings are converted to UTF-16, because that's the native
+    // charset for Windows strings.  This allows editing of the password in
+    // the Windows native UI.  But the storage for the credential is actually
+    // a little-endian blob, because passwords can contain anything.
+    let mut blob_u16 = to_wstr_no_null(password);
+    let mut blob = vec![0; blob_u16.len() * 2];
+    LittleEndian::write_u16_into(&blob_u16, &mut blob);
+    let result = set_secret(map, &blob);
+    zeroing::wipe(&mut blob_u16);
+    zeroing::wipe(&mut blob);
+    result
+}
+
+// Store the secret of a credential exactly as given, as its blob.
+pub fn set_secret(map: &PlatformCredential, secret: &[u8]) -> Result<()> {
     if let PlatformCredential::Win(map) = map {
-        validate_attributes(map, password)?;
+        validate_attributes(map, secret.len())?;
         let mut username = to_wstr(&map.username);
         let mut target_name = to_wstr(&map.target_name);
         let mut target_alias = to_wstr(&map.target_alias);
         let mut comment = to_wstr(&map.comment);
-        // Password strings are converted to UTF-16, because that's the native
-        // charset for Windows strings.  This allows editing of the password in
-        // the Windows native UI.  But the storage for the credential is actually
-        // a little-endian blob, because passwords can contain anything.
-        let blob_u16 = to_wstr_no_null(password);
-        let mut blob = vec![0; blob_u16.len() * 2];
-        LittleEndian::write_u16_into(&blob_u16, &mut blob);
+        // CredWriteW doesn't write through the blob pointer, but the
+        // structure wants a mutable one.
+        let mut blob = secret.to_vec();
         let blob_len = blob.len() as u32;
         let flags = 0;
         let cred_type = CRED_TYPE_GENERIC;
@@ -91,7 +151,9 @@ pub fn set_password(map: &PlatformCredential, password: &str) -> Result<()> {
         // raw pointer to credential, is coerced from &mut
         let pcredential: PCREDENTIALW = &mut credential;
         // Call windows API
-        match unsafe { CredWriteW(pcredential, 0) } {
+        let result = unsafe { CredWriteW(pcredential, 0) };
+        zeroing::wipe(&mut blob);
+        match result {
             0 => Err(decode_error()),
             _ => Ok(()),
         }
@@ -100,31 +162,74 @@ pub fn set_password(map: &PlatformCredential, password: &str) -> Result<()> {
     }
 }
 
+// Whether the logon session allows storing generic credentials with the
+// persistence this crate writes them with.  Without a logon session
+// (in some services, say) nothing can be stored.
+pub fn is_writable(map: &PlatformCredential) -> Result<bool> {
+    if let PlatformCredential::Win(_) = map {
+        let mut persists = [0; CRED_TYPE_MAXIMUM as usize];
+        let result = unsafe { CredGetSessionTypes(CRED_TYPE_MAXIMUM, persists.as_mut_ptr()) };
+        match result {
+            0 => match decode_error() {
+                ErrorCode::NoStorageAccess(_) => Ok(false),
+                err => Err(err),
+            },
+            _ => Ok(persists[CRED_TYPE_GENERIC as usize] >= CRED_PERSIST_ENTERPRISE),
+        }
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+// The most bytes a password can take up when stored.  The limit on a
+// credential's blob is the same for every persistence (and this crate
+// always writes with enterprise persistence).
+pub fn password_capacity() -> Option<usize> {
+    Some(CRED_MAX_CREDENTIAL_BLOB_SIZE as usize)
+}
+
+// The bytes a password takes up when stored: two for each UTF-16 code
+// unit of the blob, so two for most characters and four for the rest.
+pub fn password_len(password: &str) -> usize {
+    password.encode_utf16().count() * 2
+}
+
 pub fn get_password(map: &mut PlatformCredential) -> Result<String> {
     if let PlatformCredential::Win(map) = map {
-        validate_attributes(map, "")?;
-        let target_name = to_wstr(&map.target_name);
-        // passing uninitialized pcredential.
-        // Should be ok; it's freed by a windows api call CredFree.
-        let mut pcredential = MaybeUninit::uninit();
-        let cred_type = CRED_TYPE_GENERIC;
-        let result =
-            unsafe { CredReadW(target_name.as_ptr(), cred_type, 0, pcredential.as_mut_ptr()) };
-        match result {
-            0 => Err(decode_error()),
-            _ => {
-                let pcredential = unsafe { pcredential.assume_init() };
-                // Dereferencing pointer to credential
-                let credential: CREDENTIALW = unsafe { *pcredential };
-                decode_attributes(map, &credential);
-                let password = decode_password(&credential);
-                // Free the credential
-                unsafe {
-                    CredFree(pcredential as *mut _);
-                }
-                password
+        read_credential(map, |map, credential| {
+            decode_attributes(map, credential);
+            decode_password(credential)
+        })?
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+// Read the secret of a credential exactly as it's stored: the bytes of
+// its credential blob.
+pub fn get_secret(map: &mut PlatformCredential) -> Result<Vec<u8>> {
+    if let PlatformCredential::Win(map) = map {
+        read_credential(map, |map, credential| {
+            decode_attributes(map, credential);
+            let blob_pointer: *const u8 = credential.CredentialBlob;
+            let blob_len: usize = credential.CredentialBlobSize as usize;
+            if blob_pointer.is_null() || blob_len == 0 {
+                return Vec::new();
             }
-        }
+            unsafe { slice::from_raw_parts(blob_pointer, blob_len) }.to_vec()
+        })
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+// Read the password and the metadata of a credential in a single read,
+// so they are always consistent with each other.
+pub fn get_password_and_metadata(
+    map: &mut PlatformCredential,
+) -> Result<(String, CredentialMetadata)> {
+    if let PlatformCredential::Win(map) = map {
+        read_credential(map, decode_credential)?
     } else {
         Err(ErrorCode::WrongCredentialPlatform)
     }
@@ -132,7 +237,7 @@ pub fn get_password(map: &mut PlatformCredential) -> Result<String> {
 
 pub fn delete_password(map: &PlatformCredential) -> Result<()> {
     if let PlatformCredential::Win(map) = map {
-        validate_attributes(map, "")?;
+        validate_attributes(map, 0)?;
         let target_name = to_wstr(&map.target_name);
         let cred_type = CRED_TYPE_GENERIC;
         match unsafe { CredDeleteW(target_name.as_ptr(), cred_type, 0) } {
@@ -144,7 +249,82 @@ pub fn delete_password(map: &PlatformCredential) -> Result<()> {
     }
 }
 
-fn validate_attributes(map: &WinCredential, password: &str) -> Result<()> {
+// Read the username, comment and target alias of a stored credential
+// into the map.  (The credential manager reads the password too, but it's
+// wiped without being decoded.)
+pub fn get_attributes(map: &mut PlatformCredential) -> Result<()> {
+    if let PlatformCredential::Win(map) = map {
+        read_credential(map, decode_attributes)
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+pub fn get_metadata(map: &PlatformCredential) -> Result<CredentialMetadata> {
+    if let PlatformCredential::Win(map) = map {
+        read_credential(&mut map.clone(), |_, credential| {
+            decode_metadata(credential)
+        })
+    } else {
+        Err(ErrorCode::WrongCredentialPlatform)
+    }
+}
+
+pub fn get_access_control(map: &PlatformCredential) -> Result<AccessControl> {
+    Ok(AccessControl {
+        persistence: get_metadata(map)?.persistence,
+        ..AccessControl::default()
+    })
+}
+
+// List the generic credentials in the credential store.
+pub fn list_credentials() -> Result<Vec<PlatformCredential>> {
+    Ok(list_credentials_with_metadata()?
+        .into_iter()
+        .map(|(credential, _)| credential)
+        .collect())
+}
+
+// List the generic credentials in the credential store, with their
+// metadata, which comes with each enumerated credential.
+pub fn list_credentials_with_metadata() -> Result<Vec<(PlatformCredential, CredentialMetadata)>> {
+    let mut count = 0;
+    let mut pcredentials = MaybeUninit::uninit();
+    let result =
+        unsafe { CredEnumerateW(std::ptr::null(), 0, &mut count, pcredentials.as_mut_ptr()) };
+    if result == 0 {
+        return match decode_error() {
+            ErrorCode::NoEntry => Ok(Vec::new()),
+            err => Err(err),
+        };
+    }
+    let pcredentials = unsafe { pcredentials.assume_init() };
+    let credentials = unsafe { slice::from_raw_parts(pcredentials, count as usize) };
+    let mut found = Vec::new();
+    for &pcredential in credentials {
+        let credential: CREDENTIALW = unsafe { *pcredential };
+        if credential.Type != CRED_TYPE_GENERIC {
+            continue;
+        }
+        let mut map = WinCredential {
+            username: String::new(),
+            target_name: unsafe { from_wstr(credential.TargetName) },
+            target_alias: String::new(),
+            comment: String::new(),
+        };
+        decode_attributes(&mut map, &credential);
+        let map = PlatformCredential::Win(map);
+        if !map.is_companion() {
+            found.push((map, decode_metadata(&credential)));
+        }
+    }
+    unsafe {
+        CredFree(pcredentials as *mut _);
+    }
+    Ok(found)
+}
+
+fn validate_attributes(map: &WinCredential, secret_len: usize) -> Result<()> {
     if map.username.len() > CRED_MAX_USERNAME_LENGTH as usize {
         return Err(ErrorCode::TooLong(
             String::from("username"),
@@ -169,7 +349,7 @@ fn validate_attributes(map: &WinCredential, password: &str) -> Result<()> {
             CRED_MAX_STRING_LENGTH,
         ));
     }
-    if password.len() > CRED_MAX_CREDENTIAL_BLOB_SIZE as usize {
+    if secret_len > CRED_MAX_CREDENTIAL_BLOB_SIZE as usize {
         return Err(ErrorCode::TooLong(
             String::from("password"),
             CRED_MAX_CREDENTIAL_BLOB_SIZE,
@@ -184,20 +364,99 @@ fn decode_error() -> ErrorCode {
         ERROR_NO_SUCH_LOGON_SESSION => {
             ErrorCode::NoStorageAccess(Error(ERROR_NO_SUCH_LOGON_SESSION))
         }
+        // the user canceled a prompt for credentials
+        ERROR_CANCELLED => ErrorCode::UserCanceled(Error(ERROR_CANCELLED)),
         err => ErrorCode::PlatformFailure(Error(err)),
     }
 }
 
+// Read a credential with a single `CredReadW` call, and decode what's
+// wanted from it.  The password blob is wiped before the credential is freed.
+fn read_credential<T>(
+    map: &mut WinCredential,
+    decode: impl FnOnce(&mut WinCredential, &CREDENTIALW) -> T,
+) -> Result<T> {
+    validate_attributes(map, 0)?;
+    let target_name = to_wstr(&map.target_name);
+    // passing uninitialized pcredential.
+    // Should be ok; it's freed by a windows api call CredFree.
+    let mut pcredential = MaybeUninit::uninit();
+    let cred_type = CRED_TYPE_GENERIC;
+    let result = unsafe { CredReadW(target_name.as_ptr(), cred_type, 0, pcredential.as_mut_ptr()) };
+    match result {
+        0 => Err(decode_error()),
+        _ => {
+            let pcredential = unsafe { pcredential.assume_init() };
+            // Dereferencing pointer to credential
+            let credential: CREDENTIALW = unsafe { *pcredential };
+            let decoded = decode(map, &credential);
+            // Wipe the password blob before freeing the credential
+            if !credential.CredentialBlob.is_null() {
+                zeroing::wipe(unsafe {
+                    slice::from_raw_parts_mut(
+                        credential.CredentialBlob,
+                        credential.CredentialBlobSize as usize,
+                    )
+                });
+            }
+            // Free the credential
+            unsafe {
+                CredFree(pcredential as *mut _);
+            }
+            Ok(decoded)
+        }
+    }
+}
+
+// Decode everything there is to know about a credential: its attributes
+// into the map, and its password and metadata.
+fn decode_credential(
+    map: &mut WinCredential,
+    credential: &CREDENTIALW,
+) -> Result<(String, CredentialMetadata)> {
+    decode_attributes(map, credential);
+    Ok((decode_password(credential)?, decode_metadata(credential)))
+}
+
 fn decode_attributes(map: &mut WinCredential, credential: &CREDENTIALW) {
     map.username = unsafe { from_wstr(credential.UserName) };
     map.comment = unsafe { from_wstr(credential.Comment) };
     map.target_alias = unsafe { from_wstr(credential.TargetAlias) };
 }
 
+fn decode_metadata(credential: &CREDENTIALW) -> CredentialMetadata {
+    let persistence = match credential.Persist {
+        CRED_PERSIST_SESSION => Some(WinPersistence::Session),
+        CRED_PERSIST_LOCAL_MACHINE => Some(WinPersistence::LocalMachine),
+        CRED_PERSIST_ENTERPRISE => Some(WinPersistence::Enterprise),
+        _ => None,
+    };
+    CredentialMetadata {
+        created: None,
+        modified: decode_filetime(&credential.LastWritten),
+        persistence,
+        expires: parse_expiry(&unsafe { from_wstr(credential.Comment) }),
+    }
+}
+
+// A FILETIME counts 100-nanosecond intervals since January 1, 1601 (UTC).
+fn decode_filetime(time: &FILETIME) -> Option<SystemTime> {
+    const EPOCH_DIFFERENCE_SECS: u64 = 11_644_473_600;
+    let ticks = ((time.dwHighDateTime as u64) << 32) | time.dwLowDateTime as u64;
+    let since_1601 = Duration::from_nanos(ticks.checked_mul(100)?);
+    let since_1970 = since_1601.checked_sub(Duration::from_secs(EPOCH_DIFFERENCE_SECS))?;
+    Some(UNIX_EPOCH + since_1970)
+}
+
 fn decode_password(credential: &CREDENTIALW) -> Result<String> {
     // get password blob
     let blob_pointer: *const u8 = credential.CredentialBlob;
     let blob_len: usize = credential.CredentialBlobSize as usize;
+    // An empty password may come back with a null blob pointer, which
+    // can't be made into a slice.
+    if blob_pointer.is_null() || blob_len == 0 {
+        return Ok(String::new());
+    }
     let blob = unsafe { slice::from_raw_parts(blob_pointer, blob_len) };
     // 3rd parties may write credential data with an odd number of bytes,
     // so we make sure that we don't try to decode those as utf16
@@ -208,8 +467,10 @@ fn decode_password(credential: &CREDENTIALW) -> Result<String> {
     // Now we know this _can_ be a UTF-16 string, so convert it to
     // as UTF-16 vector and then try to decode it.
     let mut blob_u16 = vec![0; blob.len() / 2];
-    LittleEndian::read_u16_into(&blob.to_vec(), &mut blob_u16);
-    String::from_utf16(&blob_u16).map_err(|_| ErrorCode::BadEncoding(blob.to_vec()))
+    LittleEndian::read_u16_into(blob, &mut blob_u16);
+    let password = String::from_utf16(&blob_u16).map_err(|_| ErrorCode::BadEncoding(blob.to_vec()));
+    zeroing::wipe(&mut blob_u16);
+    password
 }
 
 fn to_wstr(s: &str) -> Vec<u16> {
@@ -254,6 +515,125 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_decode_filetime() {
+        // 2021-01-01T00:00:00Z
+        let ticks: u64 = (1_609_459_200 + 11_644_473_600) * 10_000_000;
+        let time = FILETIME {
+            dwLowDateTime: ticks as u32,
+            dwHighDateTime: (ticks >> 32) as u32,
+        };
+        let expected = UNIX_EPOCH + Duration::from_secs(1_609_459_200);
+        assert_eq!(decode_filetime(&time), Some(expected));
+        let unset = FILETIME {
+            dwLowDateTime: 0,
+            dwHighDateTime: 0,
+        };
+        assert_eq!(decode_filetime(&unset), None);
+    }
+
+    #[test]
+    fn test_canceled_prompt() {
+        use winapi::um::errhandlingapi::SetLastError;
+
+        unsafe { SetLastError(ERROR_CANCELLED) };
+        assert!(matches!(
+            decode_error(),
+            ErrorCode::UserCanceled(Error(ERROR_CANCELLED))
+        ));
+        unsafe { SetLastError(ERROR_NOT_FOUND) };
+        assert!(matches!(decode_error(), ErrorCode::NoEntry));
+    }
+
+    #[test]
+    fn test_error_messages() {
+        let message = error_message(&Error(ERROR_NOT_FOUND)).unwrap();
+        assert!(!message.is_empty() && message.trim_end() == message);
+        assert!(error_message(&Error(0xdead_beef)).is_none());
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        assert!(is_transient(&ErrorCode::NoStorageAccess(Error(
+            ERROR_NO_SUCH_LOGON_SESSION
+        ))));
+        assert!(!is_transient(&ErrorCode::PlatformFailure(Error(
+            ERROR_INVALID_PARAMETER
+        ))));
+        assert!(!is_transient(&ErrorCode::NoEntry));
+        assert!(ErrorCode::NoStorageAccess(Error(ERROR_NO_SUCH_LOGON_SESSION)).is_transient());
+        assert!(!ErrorCode::PlatformFailure(Error(ERROR_INVALID_PARAMETER)).is_transient());
+        assert!(!ErrorCode::UserCanceled(Error(ERROR_CANCELLED)).is_transient());
+    }
+
+    #[test]
+    fn test_empty_password() {
+        let mut credential = make_platform_credential(Vec::new());
+        credential.CredentialBlob = null_mut();
+        assert_eq!(decode_password(&credential).unwrap(), "");
+    }
+
+    #[test]
+    fn test_password_with_nul() {
+        // the blob's length, not a terminator, says where it ends
+        let mut password: Vec<u8> = "ab\0cd"
+            .encode_utf16()
+            .flat_map(|c| c.to_le_bytes())
+            .collect();
+        let credential = CREDENTIALW {
+            CredentialBlobSize: password.len() as u32,
+            CredentialBlob: password.as_mut_ptr(),
+            ..make_platform_credential(Vec::new())
+        };
+        assert_eq!(decode_password(&credential).unwrap(), "ab\0cd");
+    }
+
+    #[test]
+    fn test_decode_credential() {
+        let mut password: Vec<u8> = "secret"
+            .encode_utf16()
+            .flat_map(|c| c.to_le_bytes())
+            .collect();
+        let mut username = to_wstr("username");
+        let mut comment = to_wstr("a comment; expires 2025-01-01T00:00:00Z");
+        let mut alias = to_wstr("alias");
+        // 2021-01-01T00:00:00Z
+        let ticks: u64 = (1_609_459_200 + 11_644_473_600) * 10_000_000;
+        let credential = CREDENTIALW {
+            Comment: comment.as_mut_ptr(),
+            UserName: username.as_mut_ptr(),
+            TargetAlias: alias.as_mut_ptr(),
+            LastWritten: FILETIME {
+                dwLowDateTime: ticks as u32,
+                dwHighDateTime: (ticks >> 32) as u32,
+            },
+            CredentialBlobSize: password.len() as u32,
+            CredentialBlob: password.as_mut_ptr(),
+            Persist: CRED_PERSIST_LOCAL_MACHINE,
+            ..make_platform_credential(Vec::new())
+        };
+        let mut map = WinCredential {
+            username: String::new(),
+            target_name: "target".to_string(),
+            target_alias: String::new(),
+            comment: String::new(),
+        };
+        let (password, metadata) = decode_credential(&mut map, &credential).unwrap();
+        assert_eq!(password, "secret");
+        assert_eq!(map.username, "username");
+        assert_eq!(map.target_alias, "alias");
+        assert_eq!(map.comment, "a comment; expires 2025-01-01T00:00:00Z");
+        assert_eq!(
+            metadata.modified,
+            Some(UNIX_EPOCH + Duration::from_secs(1_609_459_200))
+        );
+        assert_eq!(metadata.persistence, Some(WinPersistence::LocalMachine));
+        assert_eq!(
+            metadata.expires,
+            Some(UNIX_EPOCH + Duration::from_secs(1_735_689_600))
+        );
+    }
+
     fn make_platform_credential(mut password: Vec<u8>) -> CREDENTIALW {
         let last_written = FILETIME {
             dwLowDateTime: 0,
@@ -277,6 +657,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_password_len() {
+        assert_eq!(password_len(""), 0);
+        assert_eq!(password_len("abc"), 6);
+        // outside the BMP, a character is two code units
+        assert_eq!(password_len("\u{1F600}"), 4);
+        let long: String = "\u{1F600}".repeat(641);
+        let map = PlatformCredential::Win(WinCredential {
+            username: "username".to_string(),
+            target_name: "target_name".to_string(),
+            target_alias: String::new(),
+            comment: String::new(),
+        });
+        validate_attribute_too_long(
+            set_password(&map, &long),
+            "password",
+            CRED_MAX_CREDENTIAL_BLOB_SIZE,
+        );
+    }
+
     #[test]
     fn test_bad_inputs() {
         let cred = WinCredential {
@@ -0,0 +1,42 @@
+/*
+With the `zeroize` feature, every buffer this crate allocates to hold
+a password on its way to or from the platform is zeroed before it is
+released, so secrets don't linger in freed memory.  Without the
+feature, wiping a buffer does nothing.
+
+Only buffers owned by this crate (or handed to it by the platform to
+free) are wiped.  A password returned to the caller, including the
+bytes attached to a `BadEncoding` error, belongs to the caller, who
+can zero it when done with it.  The exceptions are a `NativeExport`,
+a `Batch` and a `Transaction`, which wipe the passwords they hold when
+they're dropped (a transaction also wipes the previous secrets it
+saved when they're kept or rolled back).
+
+The Windows module has buffers to wipe (the UTF-16 conversions of the
+password, and the credential blob read back from the platform).  On
+Linux and Mac the password bytes returned by the platform are moved,
+not copied, into the returned string.
+ */
+
+#[cfg(feature = "zeroize")]
+pub(crate) fn wipe<T: zeroize::Zeroize + ?Sized>(buffer: &mut T) {
+    buffer.zeroize();
+}
+
+#[cfg(not(feature = "zeroize"))]
+pub(crate) fn wipe<T: ?Sized>(_buffer: &mut T) {}
+
+#[cfg(all(test, feature = "zeroize"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wipe() {
+        let mut bytes = [0x5au8; 16];
+        wipe(&mut bytes[..]);
+        assert_eq!(bytes, [0; 16]);
+        let mut wide = vec![0x5a5au16; 16];
+        wipe(&mut wide);
+        assert!(wide.is_empty());
+    }
+}
@@ -1,21 +1,79 @@
-use keyring::{credential::default_target, platform, Entry, Error};
+use keyring::{
+    audit_weak, batch, clear_confirm_hook, credential::default_target, delete_group,
+    enumerate_with_metadata, exists_many, find_modified_between, find_where, find_where_with_cap,
+    import_csv, import_csv_verified, inventory, load_aliases, platform, rekey_username,
+    remove_alias, search, self_test, serialize_aliases, set_alias, set_confirm_hook, snapshot,
+    snapshot_with_secrets, transaction, AccessSchedule, Config, Encoding, Entry, Error,
+    ImportOutcome, ImportRow, InventoryFormat, Normalization, PolicyViolation, SecretTransform,
+    TestCredential, TotpEntry, Weekday,
+};
 
 doc_comment::doctest!("../README.md");
 
 #[test]
 fn test_empty_keyring() {
-    let name = generate_random_string();
-    let entry = Entry::new(&name, &name);
+    let entry = test_credential();
     assert!(
         matches!(entry.get_password(), Err(Error::NoEntry)),
         "Read a password from a non-existent platform item"
     )
 }
 
+#[test]
+fn test_test_credential_cleanup() {
+    let entry = test_credential();
+    let same = Entry::clone(&entry);
+    entry.set_password("left behind?").unwrap();
+    drop(entry);
+    assert!(matches!(same.get_password(), Err(Error::NoEntry)));
+    // even when the test panics
+    let entry = test_credential();
+    let same = Entry::clone(&entry);
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+        entry.set_password("left behind?").unwrap();
+        panic!("in a test");
+    }));
+    assert!(panicked.is_err());
+    assert!(matches!(same.get_password(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_missing_entry_contract() {
+    let entry = test_credential();
+    assert!(matches!(entry.delete_password(), Err(Error::NoEntry)));
+    assert!(matches!(
+        entry.get_password_and_credential(),
+        Err(Error::NoEntry)
+    ));
+    entry.set_password("").unwrap();
+    let (password, _) = entry.get_password_and_credential().unwrap();
+    assert_eq!(password, "", "Empty password not read back as empty");
+    let (_, credential) = entry.get_password_and_credential().unwrap();
+    let strict = Entry::new_with_config(
+        credential.service(),
+        credential.username(),
+        Config::new().with_empty_as_corrupt(true),
+    );
+    assert!(matches!(
+        strict.get_password(),
+        Err(Error::CorruptCredential)
+    ));
+    assert!(matches!(
+        strict.get_password_and_credential(),
+        Err(Error::CorruptCredential)
+    ));
+    entry.delete_password().unwrap();
+    assert!(matches!(strict.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    assert!(
+        matches!(entry.delete_password(), Err(Error::NoEntry)),
+        "Able to delete a deleted password"
+    );
+}
+
 #[test]
 fn test_empty_password_input() {
-    let name = generate_random_string();
-    let entry = Entry::new(&name, &name);
+    let entry = test_credential();
     let in_pass = "";
     entry.set_password(in_pass).unwrap();
     let out_pass = entry.get_password().unwrap();
@@ -29,8 +87,7 @@ fn test_empty_password_input() {
 
 #[test]
 fn test_round_trip_ascii_password() {
-    let name = generate_random_string();
-    let entry = Entry::new(&name, &name);
+    let entry = test_credential();
     let password = "test ascii password";
     entry.set_password(password).unwrap();
     let stored_password = entry.get_password().unwrap();
@@ -41,8 +98,7 @@ fn test_round_trip_ascii_password() {
 
 #[test]
 fn test_round_trip_non_ascii_password() {
-    let name = generate_random_string();
-    let entry = Entry::new(&name, &name);
+    let entry = test_credential();
     let password = "このきれいな花は桜です";
     entry.set_password(password).unwrap();
     let stored_password = entry.get_password().unwrap();
@@ -52,9 +108,70 @@ fn test_round_trip_non_ascii_password() {
 }
 
 #[test]
-fn test_independent_credential_and_password() {
+fn test_round_trip_password_with_nul() {
+    // stores are length-based, so an interior NUL mustn't truncate
+    let entry = test_credential();
+    let password = "ab\0cd";
+    entry.set_password(password).unwrap();
+    let stored_password = entry.get_password().unwrap();
+    assert_eq!(stored_password.as_bytes(), b"ab\0cd");
+    entry.delete_password().unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)))
+}
+
+#[test]
+fn test_set_password_encoded() {
     let name = generate_random_string();
-    let entry = Entry::new(&name, &name);
+    let entry = Entry::new_with_config(&name, &name, Config::new().with_encoding_markers(true));
+    // these Latin-1 bytes are also valid UTF-8 (for "été"), so detection
+    // alone would decode them wrongly
+    entry
+        .set_password_encoded("\u{c3}\u{a9}t\u{c3}\u{a9}", Encoding::Latin1)
+        .unwrap();
+    assert_eq!(entry.stored_encoding().unwrap(), Some(Encoding::Latin1));
+    assert_eq!(entry.get_raw().unwrap(), b"\xc3\xa9t\xc3\xa9");
+    assert_eq!(entry.get_password().unwrap(), "\u{c3}\u{a9}t\u{c3}\u{a9}");
+    assert_eq!(
+        entry.get_password_detect().unwrap(),
+        ("\u{c3}\u{a9}t\u{c3}\u{a9}".to_string(), Encoding::Latin1)
+    );
+    // entries that don't use markers decode the platform's encoding
+    if !cfg!(target_os = "windows") {
+        assert_eq!(Entry::new(&name, &name).get_password().unwrap(), "été");
+    }
+    assert!(matches!(
+        entry.set_password_encoded("桜", Encoding::Latin1),
+        Err(Error::BadFormat(_))
+    ));
+    // setting the password in the platform's encoding removes the marker
+    entry.set_password("été").unwrap();
+    assert_eq!(entry.stored_encoding().unwrap(), None);
+    assert_eq!(entry.get_password().unwrap(), "été");
+    entry.set_password_encoded("été", Encoding::Utf8).unwrap();
+    entry.delete_password().unwrap();
+    assert_eq!(entry.stored_encoding().unwrap(), None);
+}
+
+#[test]
+fn test_get_password_detect() {
+    let entry = test_credential();
+    entry.set_password("このきれいな花は桜です").unwrap();
+    let expected = if matches!(platform(), keyring::credential::Platform::Windows) {
+        Encoding::Utf16Le
+    } else {
+        Encoding::Utf8
+    };
+    assert_eq!(
+        entry.get_password_detect().unwrap(),
+        ("このきれいな花は桜です".to_string(), expected)
+    );
+    entry.delete_password().unwrap();
+    assert!(matches!(entry.get_password_detect(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_independent_credential_and_password() {
+    let entry = test_credential();
     let password = "このきれいな花は桜です";
     entry.set_password(password).unwrap();
     let (stored_password, credential1) = entry.get_password_and_credential().unwrap();
@@ -85,6 +202,1126 @@ fn test_same_target() {
     assert!(matches!(entry2.delete_password(), Err(Error::NoEntry)))
 }
 
+#[test]
+fn test_access_log() {
+    let name = generate_random_string();
+    let entry = Entry::new(&name, &name).with_access_log(2, Some("basic test"));
+    entry.set_password("access logged").unwrap();
+    assert!(entry.access_log().unwrap().is_empty());
+    for _ in 0..3 {
+        entry.get_password().unwrap();
+    }
+    let log = entry.access_log().unwrap();
+    assert_eq!(log.len(), 2, "Access log not bounded by its capacity");
+    assert!(log[0].time <= log[1].time);
+    assert_eq!(log[1].tag.as_deref(), Some("basic test"));
+    // an entry without logging doesn't add to the log
+    Entry::new(&name, &name).get_password().unwrap();
+    assert_eq!(entry.access_log().unwrap().len(), 2);
+    entry.delete_password().unwrap();
+    assert!(
+        entry.access_log().unwrap().is_empty(),
+        "Access log survived delete"
+    );
+}
+
+#[test]
+fn test_search() {
+    let name = generate_random_string();
+    let service = format!("Search-{}-Service", name);
+    let entry1 = Entry::new(&service, "user1").with_access_log(1, None);
+    let entry2 = Entry::new(&service, "user2");
+    entry1.set_password("first").unwrap();
+    entry2.set_password("second").unwrap();
+    // produce an access log, which shouldn't show up in searches
+    entry1.get_password().unwrap();
+    let found = search(&format!("search-{}", name).to_uppercase()).unwrap();
+    assert_eq!(found.len(), 2, "Search found {:?}", found);
+    assert!(found.iter().all(|c| c.service() == service));
+    let mut users: Vec<&str> = found.iter().map(|c| c.username()).collect();
+    users.sort_unstable();
+    assert_eq!(users, ["user1", "user2"]);
+    entry1.delete_password().unwrap();
+    entry2.delete_password().unwrap();
+    assert!(search(&name).unwrap().is_empty());
+}
+
+#[test]
+fn test_confirm_hook() {
+    let name = generate_random_string();
+    let entry = Entry::new(&name, &name);
+    entry.set_password("keep me").unwrap();
+    // other tests run concurrently, so only refuse to delete this entry
+    let refused = name.clone();
+    set_confirm_hook(move |credential| credential.service() != refused);
+    assert!(matches!(entry.delete_password(), Err(Error::Cancelled)));
+    assert_eq!(entry.get_password().unwrap(), "keep me");
+    clear_confirm_hook();
+    entry.delete_password().unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_entry_confirm_hook() {
+    let name = generate_random_string();
+    let config = Config::new().with_confirm_hook(|_| false);
+    let guarded = Entry::new_with_config(&name, &name, config);
+    guarded.set_password("keep me").unwrap();
+    assert!(matches!(guarded.delete_password(), Err(Error::Cancelled)));
+    // an entry without the hook isn't affected by it
+    let unguarded = Entry::new(&name, &name);
+    assert_eq!(unguarded.get_password().unwrap(), "keep me");
+    unguarded.delete_password().unwrap();
+}
+
+#[test]
+fn test_access_control() {
+    let entry = test_credential();
+    assert!(matches!(entry.access_control(), Err(Error::NoEntry)));
+    entry.set_password("access controlled").unwrap();
+    let access = entry.access_control().unwrap();
+    assert_eq!(access.persistence.is_some(), cfg!(target_os = "windows"));
+    assert_eq!(access.locked.is_some(), cfg!(target_os = "linux"));
+    assert_eq!(access.synchronizable.is_some(), cfg!(target_os = "macos"));
+    assert_eq!(
+        access.trusted_applications.is_some(),
+        cfg!(target_os = "macos")
+    );
+    entry.delete_password().unwrap();
+}
+
+#[test]
+fn test_trailing_whitespace_normalization() {
+    let name = generate_random_string();
+    let config = Config::new().with_normalization(Normalization::TrimTrailingWhitespace);
+    let trimming = Entry::new_with_config(&name, &name, config);
+    let exact = Entry::new(&name, &name);
+    // a password written (say, by a shell tool) with a trailing newline
+    exact.set_password("from the shell\n").unwrap();
+    assert_eq!(trimming.get_password().unwrap(), "from the shell");
+    assert!(trimming.verify_password("from the shell").unwrap());
+    assert!(trimming.verify_password("from the shell\r\n").unwrap());
+    assert!(!exact.verify_password("from the shell").unwrap());
+    // and one written with normalization is stored trimmed
+    trimming.set_password("typed in \n").unwrap();
+    assert_eq!(exact.get_password().unwrap(), "typed in");
+    trimming.delete_password().unwrap();
+}
+
+#[test]
+fn test_expiry() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let entry = test_credential();
+    let expires = UNIX_EPOCH + Duration::from_secs(1_735_689_600); // 2025-01-01
+    entry.set_password_with_expiry("expiring", expires).unwrap();
+    let (password, credential) = entry.get_password_and_credential().unwrap();
+    assert_eq!(password, "expiring");
+    let expected = if cfg!(target_os = "linux") {
+        None
+    } else {
+        Some(expires)
+    };
+    assert_eq!(entry.get_metadata().unwrap().expires, expected);
+    match credential {
+        keyring::credential::PlatformCredential::Win(cred) => {
+            assert!(cred.comment.ends_with("; expires 2025-01-01T00:00:00Z"))
+        }
+        keyring::credential::PlatformCredential::Mac(cred) => {
+            assert_eq!(
+                cred.comment.as_deref(),
+                Some("expires 2025-01-01T00:00:00Z")
+            )
+        }
+        keyring::credential::PlatformCredential::Linux(_) => {}
+    }
+    entry.delete_password().unwrap();
+}
+
+#[test]
+fn test_trusted_applications() {
+    let entry = test_credential();
+    assert!(matches!(entry.trusted_applications(), Err(Error::NoEntry)));
+    entry.set_password("trusted").unwrap();
+    let applications = entry.trusted_applications().unwrap();
+    if !cfg!(target_os = "macos") {
+        assert!(applications.is_empty());
+    }
+    entry.delete_password().unwrap();
+}
+
+#[test]
+fn test_is_roaming() {
+    let entry = test_credential();
+    entry.set_password("roaming?").unwrap();
+    // this crate writes Windows credentials with enterprise persistence,
+    // and doesn't make Mac items synchronizable
+    assert_eq!(entry.is_roaming().unwrap(), cfg!(target_os = "windows"));
+    entry.delete_password().unwrap();
+    assert!(matches!(entry.is_roaming(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_delete_matching() {
+    let prefix = generate_random_string();
+    let entry1 = Entry::new(&format!("{}-one", prefix), "user");
+    let entry2 = Entry::new(&format!("{}-two", prefix), "user");
+    let other = Entry::new(&generate_random_string(), "user");
+    for entry in [&entry1, &entry2, &other] {
+        entry.set_password("bulk").unwrap();
+    }
+    let mut would_delete: Vec<String> = Entry::delete_matching_dry_run(&prefix)
+        .unwrap()
+        .iter()
+        .map(|credential| credential.service().to_string())
+        .collect();
+    would_delete.sort();
+    let expected = vec![format!("{}-one", prefix), format!("{}-two", prefix)];
+    assert_eq!(would_delete, expected);
+    // the dry run deletes nothing
+    assert_eq!(entry1.get_password().unwrap(), "bulk");
+    assert_eq!(Entry::delete_matching(&prefix).unwrap().len(), 2);
+    assert!(matches!(entry1.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(entry2.get_password(), Err(Error::NoEntry)));
+    assert!(Entry::delete_matching_dry_run(&prefix).unwrap().is_empty());
+    other.delete_password().unwrap();
+}
+
+#[test]
+fn test_delete_matching_leaves_group() {
+    let prefix = generate_random_string();
+    let service = format!("{}-grouped", prefix);
+    let member = Entry::new(&service, "user").with_group(&prefix);
+    member.set_password("grouped").unwrap();
+    assert_eq!(Entry::delete_matching(&prefix).unwrap().len(), 1);
+    // set again outside the group, so deleting the group mustn't touch it
+    let outsider = Entry::new(&service, "user");
+    outsider.set_password("no longer grouped").unwrap();
+    assert_eq!(delete_group(&prefix).unwrap(), 0);
+    assert_eq!(outsider.get_password().unwrap(), "no longer grouped");
+    outsider.delete_password().unwrap();
+}
+
+#[test]
+fn test_import_csv() {
+    let name = generate_random_string();
+    let csv = format!(
+        "service,username,password\n{0},one,\"first, password\"\n{0},two\n{0},three,third\n",
+        name
+    );
+    let report = import_csv(csv.as_bytes(), true).unwrap();
+    assert_eq!(report.rows.len(), 3);
+    assert_eq!(report.stored(), 2);
+    assert!(matches!(
+        report.rows[1].outcome,
+        ImportOutcome::Malformed(_)
+    ));
+    let one = Entry::new(&name, "one");
+    let three = Entry::new(&name, "three");
+    assert_eq!(one.get_password().unwrap(), "first, password");
+    assert_eq!(three.get_password().unwrap(), "third");
+    one.delete_password().unwrap();
+    three.delete_password().unwrap();
+}
+
+#[test]
+fn test_import_csv_verified() {
+    let name = generate_random_string();
+    let existing = Entry::new(&name, "existing");
+    let created = Entry::new(&name, "created");
+    existing.set_password("old").unwrap();
+    // a malformed row rolls back the rows that were stored
+    let csv = format!("{0},existing,new\n{0},created,new\n{0}\n", name);
+    let report = import_csv_verified(csv.as_bytes(), true).unwrap();
+    assert!(matches!(
+        report.rows[..],
+        [
+            ImportRow {
+                outcome: ImportOutcome::RolledBack,
+                ..
+            },
+            ImportRow {
+                outcome: ImportOutcome::RolledBack,
+                ..
+            },
+            ImportRow {
+                outcome: ImportOutcome::Malformed(_),
+                ..
+            },
+        ]
+    ));
+    assert_eq!(existing.get_password().unwrap(), "old");
+    assert!(matches!(created.get_password(), Err(Error::NoEntry)));
+    // without rollback, the rows that were stored stay
+    let report = import_csv_verified(csv.as_bytes(), false).unwrap();
+    assert_eq!(report.stored(), 2);
+    assert_eq!(existing.get_password().unwrap(), "new");
+    assert_eq!(created.get_password().unwrap(), "new");
+    existing.delete_password().unwrap();
+    created.delete_password().unwrap();
+}
+
+#[cfg(feature = "cache-file")]
+#[test]
+fn test_cache_file() {
+    use keyring::cache_file::CacheFile;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let name = generate_random_string();
+    let path = std::env::temp_dir().join(&name);
+    let cache = Arc::new(CacheFile::open(&path, Duration::from_secs(60)).unwrap());
+    let config = Config::new().with_cache_file(cache);
+    let cached = Entry::new_with_config(&name, &name, config);
+    let uncached = Entry::new(&name, &name);
+    cached.set_password("first").unwrap();
+    assert_eq!(cached.get_password().unwrap(), "first");
+    // a change made without the cache isn't seen while the copy is fresh,
+    // even by a later run that reopens the cache
+    uncached.set_password("second").unwrap();
+    let cache = CacheFile::open(&path, Duration::from_secs(60)).unwrap();
+    let reopened =
+        Entry::new_with_config(&name, &name, Config::new().with_cache_file(Arc::new(cache)));
+    assert_eq!(reopened.get_password().unwrap(), "first");
+    // but a change made through the cache invalidates the copy
+    reopened.set_password("third").unwrap();
+    assert_eq!(reopened.get_password().unwrap(), "third");
+    reopened.delete_password().unwrap();
+    assert!(matches!(reopened.get_password(), Err(Error::NoEntry)));
+    CacheFile::open(&path, Duration::ZERO)
+        .unwrap()
+        .destroy()
+        .unwrap();
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_case_insensitive_usernames() {
+    let name = generate_random_string();
+    let stored = Entry::new(&name, &format!("User@{}.com", name));
+    stored.set_password("case").unwrap();
+    let username = format!("user@{}.com", name.to_lowercase());
+    let exact = Entry::new(&name, &username);
+    let config = Config::new().with_case_insensitive_usernames(true);
+    let insensitive = Entry::new_with_config(&name, &username, config);
+    // Windows target names are never case-sensitive
+    if matches!(platform(), keyring::credential::Platform::Windows) {
+        assert_eq!(exact.get_password().unwrap(), "case");
+    } else {
+        assert!(matches!(exact.get_password(), Err(Error::NoEntry)));
+    }
+    assert_eq!(insensitive.get_password().unwrap(), "case");
+    let (_, credential) = insensitive.get_password_and_credential().unwrap();
+    assert_eq!(credential.username(), format!("User@{}.com", name));
+    insensitive.get_metadata().unwrap();
+    insensitive.delete_password().unwrap();
+    assert!(matches!(stored.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(insensitive.get_password(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_fallbacks() {
+    let name = generate_random_string();
+    let legacy = Entry::new(&format!("{}-v1", name), &name);
+    legacy.set_password("legacy").unwrap();
+    let missing = Entry::new(&format!("{}-v0", name), &name);
+    let entry = Entry::new(&name, &name);
+    let falling_back = entry.with_fallbacks(vec![missing, legacy.clone()]);
+    assert_eq!(falling_back.get_password().unwrap(), "legacy");
+    assert!(falling_back.verify_password("legacy").unwrap());
+    // other operations use only the primary entry
+    assert!(matches!(
+        falling_back.get_password_and_credential(),
+        Err(Error::NoEntry)
+    ));
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    let migrating = falling_back.with_fallback_migration();
+    assert_eq!(migrating.get_password().unwrap(), "legacy");
+    assert_eq!(entry.get_password().unwrap(), "legacy");
+    assert_eq!(legacy.get_password().unwrap(), "legacy");
+    entry.delete_password().unwrap();
+    legacy.delete_password().unwrap();
+    assert!(matches!(migrating.get_password(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_delete_group() {
+    let name = generate_random_string();
+    let token = Entry::new(&name, "token").with_group(&name);
+    let refresh = Entry::new(&name, "refresh").with_group(&name);
+    let left = Entry::new(&name, "left").with_group(&name);
+    let outsider = Entry::new(&name, "outsider");
+    for entry in [&token, &refresh, &left, &outsider] {
+        entry.set_password("grouped").unwrap();
+    }
+    // deleting a member's password takes it out of the group
+    left.delete_password().unwrap();
+    Entry::new(&name, "left")
+        .set_password("no longer grouped")
+        .unwrap();
+    assert_eq!(delete_group(&name).unwrap(), 2);
+    assert!(matches!(token.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(refresh.get_password(), Err(Error::NoEntry)));
+    assert_eq!(outsider.get_password().unwrap(), "grouped");
+    assert_eq!(left.get_password().unwrap(), "no longer grouped");
+    assert_eq!(delete_group(&name).unwrap(), 0);
+    outsider.delete_password().unwrap();
+    left.delete_password().unwrap();
+}
+
+#[test]
+fn test_large_group() {
+    // more members than one registry page holds on Windows
+    let name = generate_random_string();
+    let members: Vec<Entry> = (0..40)
+        .map(|n| Entry::new(&name, &format!("member {} of a large group", n)).with_group(&name))
+        .collect();
+    for member in &members {
+        member.set_password("grouped").unwrap();
+    }
+    members[0].delete_password().unwrap();
+    assert_eq!(delete_group(&name).unwrap(), members.len() - 1);
+    for member in &members {
+        assert!(matches!(member.get_password(), Err(Error::NoEntry)));
+    }
+    assert_eq!(delete_group(&name).unwrap(), 0);
+}
+
+#[test]
+fn test_password_credential_and_metadata() {
+    let entry = test_credential();
+    assert!(matches!(
+        entry.get_password_credential_and_metadata(),
+        Err(Error::NoEntry)
+    ));
+    entry.set_password("described").unwrap();
+    let (password, credential, metadata) = entry.get_password_credential_and_metadata().unwrap();
+    assert_eq!(password, "described");
+    assert_eq!(credential, entry.get_password_and_credential().unwrap().1);
+    assert_eq!(metadata, entry.get_metadata().unwrap());
+    entry.delete_password().unwrap();
+}
+
+#[test]
+fn test_exists_many() {
+    let name = generate_random_string();
+    let present = Entry::new(&name, "present");
+    let absent = Entry::new(&name, "absent");
+    present.set_password("here").unwrap();
+    assert!(present.exists().unwrap());
+    assert!(!absent.exists().unwrap());
+    let entries = vec![
+        Entry::new(&name, "absent"),
+        Entry::new(&name, "present"),
+        Entry::new(&format!("{}-other", name), "present"),
+    ];
+    assert_eq!(exists_many(&entries).unwrap(), vec![false, true, false]);
+    assert!(exists_many(&[]).unwrap().is_empty());
+    present.delete_password().unwrap();
+    assert_eq!(exists_many(&entries).unwrap(), vec![false, false, false]);
+}
+
+#[test]
+fn test_read_secret_until() {
+    use std::time::{Duration, Instant};
+
+    let entry = test_credential();
+    entry.set_password("in time").unwrap();
+    let deadline = Instant::now() + Duration::from_secs(30);
+    let (bytes, complete) = entry.read_secret_until(deadline).unwrap();
+    assert_eq!((bytes.as_slice(), complete), (&b"in time"[..], true));
+    let (bytes, complete) = entry.read_secret_until(Instant::now()).unwrap();
+    assert!(bytes.is_empty() && !complete);
+    entry.delete_password().unwrap();
+    assert!(matches!(
+        entry.read_secret_until(deadline),
+        Err(Error::NoEntry)
+    ));
+}
+
+#[test]
+fn test_access_schedule() {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    let name = generate_random_string();
+    // 2021-03-04T12:00:00Z, a Thursday
+    let noon = UNIX_EPOCH + Duration::from_secs(1_614_859_200);
+    let at = |time: SystemTime| {
+        Config::new()
+            .with_enforced_schedules(true)
+            .with_clock(move || time)
+    };
+    let entry = Entry::new_with_config(&name, &name, at(noon));
+    let business_hours = AccessSchedule {
+        days: vec![
+            Weekday::Monday,
+            Weekday::Tuesday,
+            Weekday::Wednesday,
+            Weekday::Thursday,
+            Weekday::Friday,
+        ],
+        hours: 9..17,
+    };
+    entry
+        .set_password_with_schedule("scheduled", business_hours.clone())
+        .unwrap();
+    assert_eq!(entry.get_password().unwrap(), "scheduled");
+    assert_eq!(entry.schedule().unwrap(), Some(business_hours.clone()));
+    let evening = Entry::new_with_config(&name, &name, at(noon + Duration::from_secs(8 * 3600)));
+    assert!(matches!(
+        evening.get_password(),
+        Err(Error::OutsideSchedule)
+    ));
+    // entries that don't enforce schedules can read it any time
+    assert_eq!(
+        Entry::new(&name, &name).get_password().unwrap(),
+        "scheduled"
+    );
+    // a later password doesn't inherit the schedule
+    Entry::new(&name, &name)
+        .set_password("unscheduled")
+        .unwrap();
+    assert_eq!(entry.schedule().unwrap(), None);
+    assert_eq!(evening.get_password().unwrap(), "unscheduled");
+    evening
+        .set_password_with_schedule("scheduled", business_hours)
+        .unwrap();
+    // and deleting through any entry removes it
+    Entry::new(&name, &name).delete_password().unwrap();
+    assert_eq!(entry.schedule().unwrap(), None);
+}
+
+#[test]
+fn test_get_raw() {
+    let name = generate_random_string();
+    let entry = Entry::new_with_config(
+        &name,
+        &name,
+        Config::default().with_normalization(Normalization::TrimTrailingWhitespace),
+    );
+    Entry::new(&name, &name).set_password("raw\n").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "raw");
+    let expected: Vec<u8> = if cfg!(target_os = "windows") {
+        "raw\n"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect()
+    } else {
+        b"raw\n".to_vec()
+    };
+    assert_eq!(entry.get_raw().unwrap(), expected);
+    entry.delete_password().unwrap();
+    assert!(matches!(entry.get_raw(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_batch() {
+    let name = generate_random_string();
+    let kept = Entry::new(&name, "kept");
+    let removed = Entry::new(&name, "removed");
+    let missing = Entry::new(&name, "missing");
+    removed.set_password("removed").unwrap();
+    // deleting a missing password fails, rolling back the others
+    let report = batch(|b| {
+        b.set(&kept, "kept");
+        b.delete(&removed);
+        b.delete(&missing);
+    });
+    assert_eq!(report.applied, 2);
+    assert!(matches!(report.error, Some(Error::NoEntry)));
+    assert!(report.rolled_back);
+    assert!(matches!(kept.get_password(), Err(Error::NoEntry)));
+    assert_eq!(removed.get_password().unwrap(), "removed");
+    let report = batch(|b| {
+        b.set(&kept, "kept");
+        b.delete(&removed);
+    });
+    assert_eq!(report.applied, 2);
+    assert!(report.error.is_none());
+    assert!(!report.rolled_back);
+    assert_eq!(kept.get_password().unwrap(), "kept");
+    assert!(matches!(removed.get_password(), Err(Error::NoEntry)));
+    kept.delete_password().unwrap();
+}
+
+#[test]
+fn test_transaction() {
+    let name = generate_random_string();
+    let existing = Entry::new(&name, "existing");
+    let created = Entry::new(&name, "created");
+    existing.set_password("old").unwrap();
+    let result: keyring::Result<()> = transaction(|tx| {
+        tx.set(&existing, "new")?;
+        tx.set(&created, "new")?;
+        Err(Error::Cancelled)
+    });
+    assert!(matches!(result, Err(Error::Cancelled)));
+    assert_eq!(existing.get_password().unwrap(), "old");
+    assert!(matches!(created.get_password(), Err(Error::NoEntry)));
+    // a panic rolls back too
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let _: keyring::Result<()> = transaction(|tx| {
+            tx.set(&existing, "new")?;
+            panic!("in a transaction");
+        });
+    }));
+    assert!(panicked.is_err());
+    assert_eq!(existing.get_password().unwrap(), "old");
+    // but a successful transaction keeps its writes
+    transaction(|tx| {
+        tx.set(&existing, "new")?;
+        tx.set(&created, "new")
+    })
+    .unwrap();
+    assert_eq!(existing.get_password().unwrap(), "new");
+    assert_eq!(created.get_password().unwrap(), "new");
+    existing.delete_password().unwrap();
+    created.delete_password().unwrap();
+}
+
+// A transform that marks what it stores, and reads unmarked passwords
+// (stored before the transform was used) as they are.
+struct Marked;
+
+impl SecretTransform for Marked {
+    fn on_store(&self, password: &[u8]) -> keyring::Result<Vec<u8>> {
+        Ok([b"marked:", password].concat())
+    }
+
+    fn on_load(&self, stored: &[u8]) -> keyring::Result<Vec<u8>> {
+        Ok(stored.strip_prefix(b"marked:").unwrap_or(stored).to_vec())
+    }
+}
+
+#[test]
+fn test_transaction_restores_stored_secret() {
+    let name = generate_random_string();
+    let plain = Entry::new(&name, &name);
+    let transformed = Entry::new_with_config(&name, &name, Config::new().with_transform(Marked));
+    plain.set_password("unmarked").unwrap();
+    let stored = plain.get_raw().unwrap();
+    let result: keyring::Result<()> = transaction(|tx| {
+        tx.set(&transformed, "new")?;
+        Err(Error::Cancelled)
+    });
+    assert!(matches!(result, Err(Error::Cancelled)));
+    // rolling back didn't transform the old password on the way back
+    assert_eq!(plain.get_raw().unwrap(), stored);
+    assert_eq!(plain.get_password().unwrap(), "unmarked");
+    plain.delete_password().unwrap();
+}
+
+#[test]
+fn test_enumerate_with_metadata() {
+    let prefix = generate_random_string();
+    let entry = Entry::new(&format!("{}-listed", prefix), "user");
+    entry.set_password("enumerated").unwrap();
+    let listed = enumerate_with_metadata(&prefix).unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].0.username(), "user");
+    assert_eq!(listed[0].1, entry.get_metadata().unwrap());
+    entry.delete_password().unwrap();
+    assert!(enumerate_with_metadata(&prefix).unwrap().is_empty());
+}
+
+#[test]
+fn test_audit_weak() {
+    let prefix = generate_random_string();
+    let weak = Entry::new(&format!("{}-weak", prefix), "user");
+    let strong = Entry::new(&format!("{}-strong", prefix), "user");
+    let empty = Entry::new(&format!("{}-empty", prefix), "user");
+    weak.set_password("changeme").unwrap();
+    strong.set_password("correct horse battery staple").unwrap();
+    empty.set_password("").unwrap();
+    let mut flagged = audit_weak(&["", "password", "changeme"], &prefix).unwrap();
+    flagged.sort_by(|a, b| a.service().cmp(b.service()));
+    assert_eq!(flagged.len(), 2);
+    assert_eq!(flagged[0].service(), format!("{}-empty", prefix));
+    assert_eq!(flagged[1].service(), format!("{}-weak", prefix));
+    assert!(audit_weak(&[], &prefix).unwrap().is_empty());
+    for entry in [weak, strong, empty] {
+        entry.delete_password().unwrap();
+    }
+}
+
+#[test]
+fn test_snapshot() {
+    let prefix = generate_random_string();
+    let first = Entry::new(&format!("{}-first", prefix), "user");
+    let second = Entry::new(&format!("{}-second", prefix), "user");
+    first.set_password("first password").unwrap();
+    second.set_password("second password").unwrap();
+    let taken = snapshot(&prefix).unwrap();
+    assert_eq!(taken.items.len(), 2);
+    assert!(taken.items.iter().all(|item| item.password.is_none()));
+    let with_secrets = snapshot_with_secrets(&prefix).unwrap();
+    let mut passwords: Vec<String> = with_secrets
+        .items
+        .iter()
+        .map(|item| item.password.clone().unwrap())
+        .collect();
+    passwords.sort();
+    assert_eq!(passwords, ["first password", "second password"]);
+    assert!(!format!("{:?}", with_secrets).contains("first password"));
+    // the snapshots don't change with the store
+    first.delete_password().unwrap();
+    second.delete_password().unwrap();
+    assert_eq!(taken.items.len(), 2);
+    assert_eq!(with_secrets.items.len(), 2);
+    assert!(snapshot(&prefix).unwrap().items.is_empty());
+}
+
+#[test]
+fn test_find_where() {
+    let prefix = generate_random_string();
+    let staging = Entry::new(&format!("{}-staging", prefix), "ops@example.com");
+    let production = Entry::new(&format!("{}-production", prefix), "ops@example.com");
+    staging.set_password("staging").unwrap();
+    production.set_password("production").unwrap();
+    let found = find_where(|credential| {
+        credential.service().starts_with(&prefix)
+            && credential.service().contains("staging")
+            && credential.username().contains('@')
+    })
+    .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].service(), format!("{}-staging", prefix));
+    assert!(matches!(
+        find_where_with_cap(1, |_| true),
+        Err(Error::TooManyCredentials(1))
+    ));
+    staging.delete_password().unwrap();
+    production.delete_password().unwrap();
+}
+
+#[test]
+fn test_export_native() {
+    let name = generate_random_string();
+    let entry = Entry::new(&name, &name);
+    assert!(matches!(entry.export_native(), Err(Error::NoEntry)));
+    entry.set_password("exported").unwrap();
+    let exported = entry.export_native().unwrap();
+    let everything = format!("{:?} {:?}", exported.command, exported.input);
+    assert!(everything.contains("exported"));
+    assert!(everything.contains(&name));
+    entry.delete_password().unwrap();
+}
+
+#[test]
+fn test_writable() {
+    let entry = test_credential();
+    assert!(entry.writable().unwrap());
+    // probing created nothing
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_totp_entry() {
+    let name = generate_random_string();
+    let totp = TotpEntry::new(Entry::new(&name, &name));
+    assert!(matches!(
+        totp.store_seed("Example", "me@example.com", "not base32!"),
+        Err(Error::BadFormat(_))
+    ));
+    assert!(matches!(totp.load_seed(), Err(Error::NoEntry)));
+    totp.store_seed("Example", "me@example.com", "gezd gnbv gy3t qojq")
+        .unwrap();
+    assert_eq!(totp.entry().get_password().unwrap(), "GEZDGNBVGY3TQOJQ");
+    assert_eq!(totp.load_seed().unwrap(), b"1234567890");
+    assert_eq!(
+        totp.load_label().unwrap(),
+        ("Example".to_string(), "me@example.com".to_string())
+    );
+    totp.delete_seed().unwrap();
+    assert!(matches!(totp.load_seed(), Err(Error::NoEntry)));
+    assert!(matches!(totp.load_label(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_remaining_capacity() {
+    let entry = test_credential();
+    let empty = entry.remaining_capacity().unwrap();
+    entry.set_password("four").unwrap();
+    let remaining = entry.remaining_capacity().unwrap();
+    // a password in another encoding is measured as stored
+    entry.set_password_encoded("é", Encoding::Latin1).unwrap();
+    let encoded = entry.remaining_capacity().unwrap();
+    if matches!(platform(), keyring::credential::Platform::Windows) {
+        assert_eq!(empty, Some(2560));
+        assert_eq!(remaining, Some(2552));
+        assert_eq!(encoded, Some(2559));
+    } else {
+        assert_eq!(empty, None);
+        assert_eq!(remaining, None);
+        assert_eq!(encoded, None);
+    }
+    entry.delete_password().unwrap();
+}
+
+#[test]
+fn test_provisioner() {
+    let name = generate_random_string();
+    let config = Config::new().with_provisioner(|credential| {
+        (credential.username() == "provisioned")
+            .then(|| format!("derived for {}", credential.service()))
+    });
+    let provisioned = Entry::new_with_config(&name, "provisioned", config.clone());
+    let expected = format!("derived for {}", name);
+    assert_eq!(provisioned.get_password().unwrap(), expected);
+    // the provisioned password was stored
+    assert_eq!(
+        Entry::new(&name, "provisioned").get_password().unwrap(),
+        expected
+    );
+    let declined = Entry::new_with_config(&name, "declined", config);
+    assert!(matches!(declined.get_password(), Err(Error::NoEntry)));
+    provisioned.delete_password().unwrap();
+}
+
+#[test]
+fn test_self_test() {
+    self_test().unwrap();
+    let leftovers = search("keyring-rs self-test").unwrap();
+    assert!(leftovers.iter().all(|credential| !credential
+        .username()
+        .starts_with(&format!("{}-", std::process::id()))));
+}
+
+#[test]
+fn test_update_preserves_attributes() {
+    use keyring::credential::PlatformCredential;
+
+    let name = generate_random_string();
+    let entry = Entry::new(&name, &name);
+    entry.set_password("first").unwrap();
+    let (_, original) = entry.get_password_and_credential().unwrap();
+    // another tool annotates the credential
+    let annotated = match original.clone() {
+        PlatformCredential::Linux(mut cred) => {
+            cred.label = "annotated".to_string();
+            PlatformCredential::Linux(cred)
+        }
+        PlatformCredential::Win(mut cred) => {
+            cred.comment = "annotated".to_string();
+            PlatformCredential::Win(cred)
+        }
+        PlatformCredential::Mac(mut cred) => {
+            cred.comment = Some("annotated".to_string());
+            PlatformCredential::Mac(cred)
+        }
+    };
+    let annotation = |credential: &PlatformCredential| match credential {
+        PlatformCredential::Linux(cred) => Some(cred.label.clone()),
+        PlatformCredential::Win(cred) => Some(cred.comment.clone()),
+        PlatformCredential::Mac(cred) => cred.comment.clone(),
+    };
+    Entry::new_with_credential(&annotated)
+        .unwrap()
+        .set_password("first")
+        .unwrap();
+    // updating just the password keeps the annotation
+    entry.set_password("second").unwrap();
+    let (password, updated) = entry.get_password_and_credential().unwrap();
+    assert_eq!(password, "second");
+    assert_eq!(annotation(&updated).as_deref(), Some("annotated"));
+    // unless the entry resets attributes (which Mac items can't)
+    let resetting = Config::new().with_reset_attributes(true);
+    Entry::new_with_config(&name, &name, resetting)
+        .set_password("third")
+        .unwrap();
+    let (_, reset) = entry.get_password_and_credential().unwrap();
+    if cfg!(target_os = "macos") {
+        assert_eq!(annotation(&reset).as_deref(), Some("annotated"));
+    } else {
+        assert_eq!(annotation(&reset), annotation(&original));
+    }
+    entry.delete_password().unwrap();
+}
+
+#[test]
+fn test_find_modified_between() {
+    use std::time::{Duration, SystemTime};
+
+    let name = generate_random_string();
+    let entry = Entry::new(&name, &name);
+    // platform times may only be accurate to the second
+    let before_write = SystemTime::now() - Duration::from_secs(2);
+    entry.set_password("recent").unwrap();
+    let after_write = SystemTime::now() + Duration::from_secs(2);
+    let found = find_modified_between(before_write, after_write).unwrap();
+    assert!(found.iter().any(|credential| credential.service() == name));
+    let earlier =
+        find_modified_between(before_write - Duration::from_secs(3600), before_write).unwrap();
+    assert!(!earlier
+        .iter()
+        .any(|credential| credential.service() == name));
+    entry.delete_password().unwrap();
+}
+
+#[test]
+fn test_aliases() {
+    let name = generate_random_string();
+    let alias = format!("{}-primary", name);
+    assert!(matches!(
+        Entry::by_alias(&alias),
+        Err(Error::UnknownAlias(_))
+    ));
+    let first = default_target(&platform(), None, &name, "first");
+    let second = default_target(&platform(), None, &name, "second");
+    set_alias(&alias, &first).unwrap();
+    Entry::by_alias(&alias)
+        .unwrap()
+        .set_password("aliased")
+        .unwrap();
+    assert_eq!(
+        Entry::new(&name, "first").get_password().unwrap(),
+        "aliased"
+    );
+    // repointing the alias changes the credential it names
+    set_alias(&alias, &second).unwrap();
+    assert!(matches!(
+        Entry::by_alias(&alias).unwrap().get_password(),
+        Err(Error::NoEntry)
+    ));
+    let saved = serialize_aliases();
+    assert!(remove_alias(&alias));
+    assert!(!remove_alias(&alias));
+    load_aliases(&saved).unwrap();
+    // a bad line leaves the loaded aliases in place
+    assert!(matches!(
+        load_aliases("not an alias"),
+        Err(Error::BadFormat(_))
+    ));
+    let repointed = Entry::by_alias(&alias).unwrap();
+    repointed.set_password("second").unwrap();
+    assert_eq!(
+        Entry::new(&name, "second").get_password().unwrap(),
+        "second"
+    );
+    repointed.delete_password().unwrap();
+    Entry::new(&name, "first").delete_password().unwrap();
+    remove_alias(&alias);
+}
+
+#[cfg(feature = "handoff")]
+#[test]
+fn test_handoff() {
+    use keyring::HandoffToken;
+
+    let name = generate_random_string();
+    let entry = Entry::new(&name, &name);
+    let token = entry.set_handoff("handed off").unwrap();
+    // only the token reads the secret, and a wrong one leaves it in place
+    let wrong = HandoffToken("00".repeat(32));
+    assert!(matches!(
+        entry.read_handoff(&wrong),
+        Err(Error::BadHandoffToken)
+    ));
+    assert!(matches!(
+        entry.read_handoff(&HandoffToken("garbage".to_string())),
+        Err(Error::BadHandoffToken)
+    ));
+    // the receiving process has its own entry
+    let receiver = Entry::new(&name, &name);
+    assert_eq!(receiver.read_handoff(&token).unwrap(), "handed off");
+    assert!(matches!(receiver.read_handoff(&token), Err(Error::NoEntry)));
+    // the handoff is separate from the password
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    assert_eq!(format!("{:?}", token), "HandoffToken(..)");
+}
+
+#[test]
+fn test_policy() {
+    let name = generate_random_string();
+    let config = Config::new().with_policy(|secret| {
+        if secret.len() < 8 {
+            Err(PolicyViolation("shorter than 8 bytes".to_string()))
+        } else {
+            Ok(())
+        }
+    });
+    let entry = Entry::new_with_config(&name, &name, config);
+    match entry.set_password("short") {
+        Err(Error::PolicyViolation(reason)) => assert_eq!(reason.0, "shorter than 8 bytes"),
+        other => panic!("Short password not rejected: {:?}", other),
+    }
+    assert!(matches!(entry.get_password(), Err(Error::NoEntry)));
+    entry.set_password("long enough").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "long enough");
+    entry.delete_password().unwrap();
+}
+
+struct Xor(u8);
+
+impl SecretTransform for Xor {
+    fn on_store(&self, password: &[u8]) -> keyring::Result<Vec<u8>> {
+        Ok(password.iter().map(|byte| byte ^ self.0).collect())
+    }
+
+    fn on_load(&self, stored: &[u8]) -> keyring::Result<Vec<u8>> {
+        self.on_store(stored)
+    }
+}
+
+#[test]
+fn test_transform() {
+    let name = generate_random_string();
+    let entry = Entry::new_with_config(&name, &name, Config::new().with_transform(Xor(0x20)));
+    entry.set_password("AB").unwrap();
+    assert_eq!(entry.get_password().unwrap(), "AB");
+    assert_eq!(entry.get_password_and_credential().unwrap().0, "AB");
+    // what's stored is the transformed password, in hex
+    let plain = Entry::new(&name, &name);
+    assert_eq!(plain.get_password().unwrap(), "6162");
+    plain.set_password("not hex").unwrap();
+    assert!(matches!(entry.get_password(), Err(Error::BadFormat(_))));
+    entry.delete_password().unwrap();
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_collection_policy() {
+    let name = generate_random_string();
+    let store = format!("{}-collection", name);
+    let required = Entry::new_with_config(&name, &name, Config::new().with_store(&store));
+    match required.set_password("nowhere") {
+        Err(Error::NoSuchCollection(missing)) => assert_eq!(missing, store),
+        other => panic!("Password stored in a missing collection: {:?}", other),
+    }
+    assert!(matches!(
+        required.get_password(),
+        Err(Error::NoSuchCollection(_))
+    ));
+    let created = Entry::new_with_config(
+        &name,
+        &name,
+        Config::new()
+            .with_store(&store)
+            .with_collection_policy(keyring::CollectionPolicy::CreateIfMissing),
+    );
+    created.set_password("somewhere").unwrap();
+    assert_eq!(required.get_password().unwrap(), "somewhere");
+    // the default collection was left alone
+    assert!(matches!(
+        Entry::new(&name, &name).get_password(),
+        Err(Error::NoEntry)
+    ));
+    created.delete_password().unwrap();
+}
+
+#[test]
+fn test_creating_app() {
+    let entry = test_credential();
+    assert!(matches!(entry.creating_app(), Err(Error::NoEntry)));
+    entry.set_password("created").unwrap();
+    let creator = entry.creating_app().unwrap();
+    if cfg!(target_os = "linux") {
+        assert_eq!(creator.as_deref(), Some("rust-keyring"));
+    } else if cfg!(target_os = "windows") {
+        assert_eq!(creator, None);
+    } else {
+        assert!(creator.is_some());
+    }
+    entry.delete_password().unwrap();
+}
+
+#[test]
+fn test_password_context() {
+    let name = generate_random_string();
+    let entry = Entry::new(&name, &name);
+    entry.set_password("no context").unwrap();
+    assert_eq!(entry.context().unwrap(), None);
+    assert_eq!(
+        entry.get_password_for_context("prod-db").unwrap(),
+        "no context"
+    );
+    entry
+        .set_password_with_context("staging password", "staging-db")
+        .unwrap();
+    assert_eq!(entry.context().unwrap().as_deref(), Some("staging-db"));
+    assert_eq!(
+        entry.get_password_for_context("staging-db").unwrap(),
+        "staging password"
+    );
+    assert!(matches!(
+        entry.get_password_for_context("prod-db"),
+        Err(Error::ContextMismatch(stored, requested)) if stored == "staging-db" && requested == "prod-db"
+    ));
+    // other reads ignore the context
+    assert_eq!(entry.get_password().unwrap(), "staging password");
+    // setting the password without a context removes it
+    entry.set_password("no context again").unwrap();
+    assert_eq!(entry.context().unwrap(), None);
+    entry
+        .set_password_with_context("staging password", "staging-db")
+        .unwrap();
+    entry.delete_password().unwrap();
+    assert_eq!(entry.context().unwrap(), None);
+}
+
+#[test]
+fn test_rekey_username() {
+    let name = generate_random_string();
+    let guest = Entry::new(&name, "guest");
+    let registered = Entry::new(&name, "registered");
+    assert!(!rekey_username(&name, "guest", "registered").unwrap());
+    guest.set_password("guest password").unwrap();
+    assert!(rekey_username(&name, "guest", "registered").unwrap());
+    assert_eq!(registered.get_password().unwrap(), "guest password");
+    assert!(matches!(guest.get_password(), Err(Error::NoEntry)));
+    registered.delete_password().unwrap();
+}
+
+#[test]
+fn test_rekey_username_moves_stored_secret_and_companions() {
+    let name = generate_random_string();
+    let guest = Entry::new(&name, "guest").with_group(&name);
+    let registered = Entry::new(&name, "registered");
+    Entry::new(&name, "registered")
+        .set_password("replaced")
+        .unwrap();
+    guest
+        .set_password_with_context("guest password\n", "prod-db")
+        .unwrap();
+    let stored = guest.get_raw().unwrap();
+    assert!(rekey_username(&name, "guest", "registered").unwrap());
+    assert_eq!(registered.get_raw().unwrap(), stored);
+    assert_eq!(registered.context().unwrap().as_deref(), Some("prod-db"));
+    assert_eq!(guest.context().unwrap(), None);
+    // the new credential took the old one's place in the group
+    assert_eq!(delete_group(&name).unwrap(), 1);
+    assert!(matches!(registered.get_password(), Err(Error::NoEntry)));
+    assert!(matches!(guest.get_password(), Err(Error::NoEntry)));
+}
+
+#[test]
+fn test_inventory() {
+    let name = generate_random_string();
+    let entry = Entry::new(&name, &name);
+    entry.set_password("never reported").unwrap();
+    let metadata = entry.get_metadata().unwrap();
+    if !cfg!(target_os = "windows") {
+        assert!(metadata.created.is_some());
+    }
+    assert!(metadata.modified.is_some());
+    let csv = inventory(&name, InventoryFormat::Csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("service,username,store,"));
+    assert!(lines[1].starts_with(&format!("{},{},", name, name)));
+    let json = inventory(&name, InventoryFormat::Json).unwrap();
+    assert!(json.contains(&format!("\"service\":\"{}\"", name)));
+    assert!(!csv.contains("never reported") && !json.contains("never reported"));
+    entry.delete_password().unwrap();
+    assert_eq!(inventory(&name, InventoryFormat::Json).unwrap(), "[]");
+    assert!(matches!(entry.get_metadata(), Err(Error::NoEntry)));
+}
+
+// An entry with a random service and username, for a test to store a
+// password in, that deletes its password when it's dropped.
+fn test_credential() -> TestCredential {
+    TestCredential::new(&generate_random_string())
+}
+
 fn generate_random_string() -> String {
     // from the Rust Cookbook:
     // https://rust-lang-nursery.github.io/rust-cookbook/algorithms/randomness.html